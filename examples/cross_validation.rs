@@ -0,0 +1,17 @@
+// k-fold search of the L2 lambda on the preloaded moons dataset
+use oxigrad::data::{INP_DATASET, LBLS_DATASET};
+use oxigrad::error::OxiError;
+use oxigrad::utils::{alpha, mse};
+use oxigrad::xval::{XVal, FloatingRange};
+
+fn main() -> Result<(), OxiError> {
+    let data: Vec<Vec<f64>> = INP_DATASET.iter().map(|r| r.to_vec()).collect();
+    let arch = [4, 1];
+
+    let mut xv = XVal::new(data, LBLS_DATASET.to_vec(), &arch, FloatingRange::new(0.0, 0.004, 0.001), alpha, mse, 5)?;
+    let lambda = xv.search_best_hyperpar()?;
+
+    println!("best L2 lambda={:.4}, accuracy={:.0}%", lambda, xv.best_score().unwrap_or(0.0) * 100.0);
+
+    Ok(())
+}
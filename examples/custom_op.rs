@@ -0,0 +1,36 @@
+// a differentiable operation defined outside of the engine, used as any other operation on Values
+use std::rc::Rc;
+use oxigrad::engine::{CustomOp, Value};
+
+// softplus(x) = ln(1 + e^x)
+struct Softplus;
+
+impl CustomOp for Softplus {
+    fn name(&self) -> &str {
+        "softplus"
+    }
+
+    fn forward(&self, inputs: &[f64]) -> f64 {
+        inputs[0].exp().ln_1p()
+    }
+
+    fn backward(&self, inputs: &[f64], grad: f64) -> Vec<f64> {
+        // the derivative of softplus is the sigmoid
+        vec![grad / (1.0 + (-inputs[0]).exp())]
+    }
+}
+
+fn main() {
+    let w = Value::new(0.5);
+    let x = Value::new(-2.0);
+
+    // e = softplus(w * x) * w
+    let s = Value::custom(Rc::new(Softplus), &[&w * &x]);
+    let e = &s * &w;
+    e.backward();
+
+    println!("e = {}", e.get_data());
+    println!("de/dw = {}", w.get_grad());
+    println!("de/dx = {}", x.get_grad());
+    println!("graph:\n{}", e.dump());
+}
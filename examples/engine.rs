@@ -0,0 +1,18 @@
+// gradients of a small expression computed by the autograd engine
+use oxigrad::engine::Value;
+
+fn main() {
+    let a = Value::new(2.0);
+    let b = Value::new(-3.0);
+    let c = Value::new(10.0);
+
+    // e = (a * b + c)^2 / 4, relu'd
+    let d = &(&a * &b) + &c;
+    let e = (d.power(2.0) / 4.0).relu();
+    e.backward();
+
+    println!("e = {}", e.get_data());
+    println!("de/da = {}", a.get_grad());
+    println!("de/db = {}", b.get_grad());
+    println!("de/dc = {}", c.get_grad());
+}
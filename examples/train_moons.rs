@@ -0,0 +1,29 @@
+// a small model trained on generated data with the library's training loop
+use oxigrad::callbacks::{CallbackList, EarlyStopping};
+use oxigrad::data::make_moons;
+use oxigrad::error::OxiError;
+use oxigrad::nn::Model;
+use oxigrad::optim::Adam;
+use oxigrad::preprocessing::{StandardScaler, Transformer};
+use oxigrad::train::{fit, evaluate, TrainOptions};
+use oxigrad::utils::{mse, train_test_split};
+
+fn main() -> Result<(), OxiError> {
+    let (data, labels) = make_moons(200, 0.1, 7);
+    let ((train_x, train_y), (test_x, test_y)) = train_test_split(&data, &labels, 0.25, 7, true)?;
+
+    let mut scaler = StandardScaler::new();
+    let train_x = scaler.fit_transform(&train_x);
+    let test_x = scaler.transform(&test_x);
+
+    let m = Model::new(2, &[8, 8, 1]);
+    let options = TrainOptions { epochs: 100, verbose: false, ..TrainOptions::default() };
+    let mut callbacks = CallbackList::new(vec![Box::new(EarlyStopping::new("val_loss").patience(10).restore_best_weights(true))]);
+    let history = fit(&m, &mut Adam::new(0.02), (&train_x, &train_y), Some((&test_x, &test_y)), &options, &mut callbacks)?;
+
+    let result = evaluate(&m, &test_x, &test_y, mse);
+    println!("trained for {} passes", history.len());
+    println!("test loss={:.4}, accuracy={:.0}%", result.loss, result.accuracy * 100.0);
+
+    Ok(())
+}
@@ -0,0 +1,382 @@
+use std::fmt;
+use std::str::FromStr;
+use oxigrad::config::MAX_LAMBDAS;
+use oxigrad::optim;
+use oxigrad::train::LOSSES;
+
+pub const USAGE: &str = "\
+Usage: oxigrad <command> [options]
+
+Commands:
+  demo [DATA]                 train on the preloaded moons dataset (or on a CSV file) showing every step
+  run EXPERIMENT              run the experiment described by a JSON file (see experiments/moons.json)
+  train DATA [options]        train a model on a CSV file and save it
+      --arch LIST             neurons of each layer, comma separated (default 16,16,1)
+      --loss NAME             mse or hinge (default mse)
+      --optimizer NAME        sgd, momentum or adam (default sgd)
+      --lr RATE               learning rate (default 0.03)
+      --epochs N              training passes over the whole data (default 50)
+      --l2 LAMBDA             L2 regularization strength (default 0)
+      --validation RATIO      fraction of the data held out for validation (default 0.2)
+      --patience N            stop after N passes without improving the validation loss
+      --seed N                seed of the train/validation split (default 42)
+      --output FILE           where the trained model is saved (default model.json)
+  eval MODEL DATA [options]   loss and accuracy of a saved model on a CSV file
+      --loss NAME             mse or hinge (default mse)
+  predict MODEL INPUTS...     outputs of a saved model, each input being a comma separated list of features
+  xval DATA [options]         k-fold cross validation of the L2 lambda for one or more architectures
+      --arch LIST             architecture to evaluate, can be repeated (default 16,16,1)
+      --lambda START:END:STEP range of L2 lambdas (default 0:0.01:0.0005)
+      --k N                   number of folds (default 10)
+      --loss NAME             mse or hinge (default mse)
+  help                        print this message
+
+CSV files hold numeric features followed by the label in the last column, header row optional.";
+
+// WRONG COMMAND LINE ERROR
+#[derive(Debug, PartialEq)]
+pub struct UsageError(pub String);
+
+impl fmt::Display for UsageError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+// COMMANDS
+#[derive(Debug, PartialEq)]
+pub struct TrainArgs {
+    pub data: String,
+    pub arch: Vec<usize>,
+    pub loss: String,
+    pub optimizer: String,
+    pub lr: f64,
+    pub epochs: usize,
+    pub l2: f64,
+    pub validation: f64,
+    pub patience: Option<usize>,
+    pub seed: u64,
+    pub output: String,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct XValArgs {
+    pub data: String,
+    pub archs: Vec<Vec<usize>>,
+    pub lambdas: (f64, f64, f64),
+    pub k: usize,
+    pub loss: String,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Command {
+    Demo { data: Option<String> },
+    Run { experiment: String },
+    Train(TrainArgs),
+    Eval { model: String, data: String, loss: String },
+    Predict { model: String, inputs: Vec<Vec<f64>> },
+    XVal(XValArgs),
+    Help,
+}
+
+// ARGUMENTS PARSING
+// positional arguments and "--name value" options of a command, in the given order
+struct Args {
+    positional: Vec<String>,
+    options: Vec<(String, String)>,
+}
+
+impl Args {
+    fn split(command: &str, args: &[String], allowed: &[&str]) -> Result<Self, UsageError> {
+        let mut parsed = Args { positional: Vec::new(), options: Vec::new() };
+        let mut iter = args.iter();
+
+        while let Some(arg) = iter.next() {
+            match arg.strip_prefix("--") {
+                Some(name) => {
+                    if !allowed.contains(&name) {
+                        return Err(UsageError(format!("unknown option --{} for {}", name, command)));
+                    }
+                    let value = iter.next()
+                        .ok_or_else(|| UsageError(format!("missing value for --{}", name)))?;
+                    parsed.options.push((name.to_string(), value.clone()));
+                }
+                None => parsed.positional.push(arg.clone()),
+            }
+        }
+
+        Ok(parsed)
+    }
+
+    // last value given for an option
+    fn option(&self, name: &str) -> Option<&str> {
+        self.options.iter().rev().find(|(n, _)| n == name).map(|(_, v)| v.as_str())
+    }
+
+    // every value given for an option
+    fn all(&self, name: &str) -> Vec<&str> {
+        self.options.iter().filter(|(n, _)| n == name).map(|(_, v)| v.as_str()).collect()
+    }
+
+    fn parsed<T: FromStr>(&self, name: &str, default: T, expected: &str) -> Result<T, UsageError> {
+        match self.option(name) {
+            Some(v) => number(name, v, expected),
+            None => Ok(default),
+        }
+    }
+
+    fn positionals(&self, command: &str, names: &[&str]) -> Result<(), UsageError> {
+        if self.positional.len() < names.len() {
+            return Err(UsageError(format!("missing {} for {}", names[self.positional.len()], command)));
+        }
+        if self.positional.len() > names.len() {
+            return Err(UsageError(format!("unexpected argument '{}' for {}", self.positional[names.len()], command)));
+        }
+        Ok(())
+    }
+}
+
+fn number<T: FromStr>(name: &str, value: &str, expected: &str) -> Result<T, UsageError> {
+    value.parse()
+        .map_err(|_| UsageError(format!("invalid value '{}' for --{}: expected {}", value, name, expected)))
+}
+
+fn arch(value: &str) -> Result<Vec<usize>, UsageError> {
+    let layers: Result<Vec<usize>, _> = value.split(',').map(|n| n.trim().parse()).collect();
+    match layers {
+        Ok(l) if !l.is_empty() && l.iter().all(|n| *n > 0) && l.last() == Some(&1) => Ok(l),
+        _ => Err(UsageError(format!(
+            "invalid value '{}' for --arch: expected positive layer sizes separated by commas, the last one being 1",
+            value,
+        ))),
+    }
+}
+
+fn named(name: &str, value: &str, known: &[&str]) -> Result<String, UsageError> {
+    if known.contains(&value) {
+        Ok(value.to_string())
+    } else {
+        Err(UsageError(format!("unknown {} '{}': expected one of {}", name, value, known.join(", "))))
+    }
+}
+
+fn inputs(value: &str) -> Result<Vec<f64>, UsageError> {
+    value.split(',')
+        .map(|v| v.trim().parse::<f64>())
+        .collect::<Result<Vec<f64>, _>>()
+        .map_err(|_| UsageError(format!("invalid input '{}': expected numbers separated by commas", value)))
+}
+
+fn ratio(name: &str, value: f64) -> Result<f64, UsageError> {
+    if (0.0..1.0).contains(&value) {
+        Ok(value)
+    } else {
+        Err(UsageError(format!("invalid value '{}' for --{}: expected a number from 0 (included) to 1 (excluded)", value, name)))
+    }
+}
+
+// same checks (and messages) as Experiment::validate for the training settings
+fn positive(name: &str, value: f64) -> Result<f64, UsageError> {
+    if value.is_finite() && value > 0.0 {
+        Ok(value)
+    } else {
+        Err(UsageError(format!("invalid value '{}' for --{}: expected a positive number", value, name)))
+    }
+}
+
+fn non_negative(name: &str, value: f64) -> Result<f64, UsageError> {
+    if value.is_finite() && value >= 0.0 {
+        Ok(value)
+    } else {
+        Err(UsageError(format!("invalid value '{}' for --{}: expected a non-negative number", value, name)))
+    }
+}
+
+fn epochs(value: usize) -> Result<usize, UsageError> {
+    if value > 0 {
+        Ok(value)
+    } else {
+        Err(UsageError(format!("invalid value '{}' for --epochs: expected at least 1 epoch", value)))
+    }
+}
+
+fn lambdas(value: &str) -> Result<(f64, f64, f64), UsageError> {
+    let err = || UsageError(format!(
+        "invalid value '{}' for --lambda: expected START:END:STEP with 0 <= START <= END < inf, STEP > 0 and at most {} lambdas",
+        value,
+        MAX_LAMBDAS,
+    ));
+    let parts: Vec<f64> = value.split(':')
+        .map(|v| v.trim().parse::<f64>())
+        .collect::<Result<Vec<f64>, _>>()
+        .map_err(|_| err())?;
+
+    match parts[..] {
+        [start, end, step] if start >= 0.0 && start <= end && end.is_finite() && step.is_finite() && step > 0.0
+            && ((end - start) / step).floor() + 1.0 <= MAX_LAMBDAS => Ok((start, end, step)),
+        _ => Err(err()),
+    }
+}
+
+pub fn parse(args: &[String]) -> Result<Command, UsageError> {
+    let (command, rest) = match args.split_first() {
+        Some((c, rest)) => (c.as_str(), rest),
+        None => return Err(UsageError(String::from("missing command"))),
+    };
+
+    match command {
+        "demo" => {
+            let a = Args::split(command, rest, &[])?;
+            if a.positional.len() > 1 {
+                return Err(UsageError(format!("unexpected argument '{}' for demo", a.positional[1])));
+            }
+            Ok(Command::Demo { data: a.positional.first().cloned() })
+        }
+        "run" => {
+            let a = Args::split(command, rest, &[])?;
+            a.positionals(command, &["EXPERIMENT"])?;
+
+            Ok(Command::Run { experiment: a.positional[0].clone() })
+        }
+        "train" => {
+            let a = Args::split(
+                command,
+                rest,
+                &["arch", "loss", "optimizer", "lr", "epochs", "l2", "validation", "patience", "seed", "output"],
+            )?;
+            a.positionals(command, &["DATA"])?;
+
+            Ok(Command::Train(TrainArgs {
+                data: a.positional[0].clone(),
+                arch: arch(a.option("arch").unwrap_or("16,16,1"))?,
+                loss: named("loss", a.option("loss").unwrap_or("mse"), &LOSSES)?,
+                optimizer: named("optimizer", a.option("optimizer").unwrap_or("sgd"), &optim::NAMES)?,
+                lr: positive("lr", a.parsed("lr", 0.03, "a number")?)?,
+                epochs: epochs(a.parsed("epochs", 50, "a positive integer")?)?,
+                l2: non_negative("l2", a.parsed("l2", 0.0, "a number")?)?,
+                validation: ratio("validation", a.parsed("validation", 0.2, "a number")?)?,
+                patience: a.option("patience").map(|v| number("patience", v, "a positive integer")).transpose()?,
+                seed: a.parsed("seed", 42, "a positive integer")?,
+                output: a.option("output").unwrap_or("model.json").to_string(),
+            }))
+        }
+        "eval" => {
+            let a = Args::split(command, rest, &["loss"])?;
+            a.positionals(command, &["MODEL", "DATA"])?;
+
+            Ok(Command::Eval {
+                model: a.positional[0].clone(),
+                data: a.positional[1].clone(),
+                loss: named("loss", a.option("loss").unwrap_or("mse"), &LOSSES)?,
+            })
+        }
+        "predict" => {
+            // negative numbers look like options, so everything after the model is taken as an input
+            match rest.split_first() {
+                None => Err(UsageError(String::from("missing MODEL for predict"))),
+                Some((_, [])) => Err(UsageError(String::from("missing INPUTS for predict"))),
+                Some((model, values)) => Ok(Command::Predict {
+                    model: model.clone(),
+                    inputs: values.iter().map(|v| inputs(v)).collect::<Result<_, _>>()?,
+                }),
+            }
+        }
+        "xval" => {
+            let a = Args::split(command, rest, &["arch", "lambda", "k", "loss"])?;
+            a.positionals(command, &["DATA"])?;
+            let archs = a.all("arch");
+
+            let k: usize = a.parsed("k", 10, "an integer greater than 1")?;
+            if k < 2 {
+                return Err(UsageError(format!("invalid value '{}' for --k: expected an integer greater than 1", k)));
+            }
+
+            Ok(Command::XVal(XValArgs {
+                data: a.positional[0].clone(),
+                archs: if archs.is_empty() {
+                    vec![vec![16, 16, 1]]
+                } else {
+                    archs.iter().map(|v| arch(v)).collect::<Result<_, _>>()?
+                },
+                lambdas: lambdas(a.option("lambda").unwrap_or("0:0.01:0.0005"))?,
+                k,
+                loss: named("loss", a.option("loss").unwrap_or("mse"), &LOSSES)?,
+            }))
+        }
+        "help" | "--help" | "-h" => Ok(Command::Help),
+        _ => Err(UsageError(format!("unknown command '{}'", command))),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn args(line: &str) -> Vec<String> {
+        line.split_whitespace().map(String::from).collect()
+    }
+
+    #[test]
+    fn test_train() {
+        let cmd = parse(&args("train data.csv --arch 8,1 --optimizer adam --lr 0.01 --patience 5")).unwrap();
+        let expected = TrainArgs {
+            data: String::from("data.csv"),
+            arch: vec![8, 1],
+            loss: String::from("mse"),
+            optimizer: String::from("adam"),
+            lr: 0.01,
+            epochs: 50,
+            l2: 0.0,
+            validation: 0.2,
+            patience: Some(5),
+            seed: 42,
+            output: String::from("model.json"),
+        };
+
+        assert_eq!(cmd, Command::Train(expected));
+    }
+
+    #[test]
+    fn test_predict_and_xval() {
+        assert_eq!(
+            parse(&args("predict model.json 1,-2.5 -0.5,0")).unwrap(),
+            Command::Predict { model: String::from("model.json"), inputs: vec![vec![1.0, -2.5], vec![-0.5, 0.0]] },
+        );
+
+        match parse(&args("xval data.csv --arch 4,1 --arch 8,8,1 --lambda 0:0.1:0.05 --k 5")).unwrap() {
+            Command::XVal(x) => {
+                assert_eq!(x.archs, vec![vec![4, 1], vec![8, 8, 1]]);
+                assert_eq!(x.lambdas, (0.0, 0.1, 0.05));
+                assert_eq!(x.k, 5);
+            }
+            other => panic!("unexpected command {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_usage_errors() {
+        let error = |line: &str| parse(&args(line)).unwrap_err().0;
+
+        assert_eq!(error(""), "missing command");
+        assert_eq!(error("fit data.csv"), "unknown command 'fit'");
+        assert_eq!(error("train"), "missing DATA for train");
+        assert_eq!(error("train a.csv b.csv"), "unexpected argument 'b.csv' for train");
+        assert_eq!(error("train a.csv --epoch 3"), "unknown option --epoch for train");
+        assert_eq!(error("train a.csv --epochs"), "missing value for --epochs");
+        assert_eq!(error("train a.csv --epochs ten"), "invalid value 'ten' for --epochs: expected a positive integer");
+        assert_eq!(error("train a.csv --epochs 0"), "invalid value '0' for --epochs: expected at least 1 epoch");
+        assert_eq!(error("train a.csv --lr 0"), "invalid value '0' for --lr: expected a positive number");
+        assert_eq!(error("train a.csv --lr NaN"), "invalid value 'NaN' for --lr: expected a positive number");
+        assert_eq!(error("train a.csv --l2 inf"), "invalid value 'inf' for --l2: expected a non-negative number");
+        assert_eq!(error("train a.csv --optimizer rmsprop"), "unknown optimizer 'rmsprop': expected one of sgd, momentum, adam");
+        assert!(error("train a.csv --arch 16,0,1").starts_with("invalid value '16,0,1' for --arch"));
+        assert!(error("train a.csv --validation 1").starts_with("invalid value '1' for --validation"));
+        assert_eq!(error("run"), "missing EXPERIMENT for run");
+        assert_eq!(error("eval model.json"), "missing DATA for eval");
+        assert_eq!(error("predict model.json 1,x"), "invalid input '1,x': expected numbers separated by commas");
+        assert!(error("xval a.csv --lambda 1:0:0.1").starts_with("invalid value '1:0:0.1' for --lambda"));
+        assert!(error("xval a.csv --lambda 0:inf:0.1").starts_with("invalid value '0:inf:0.1' for --lambda"));
+        assert!(error("xval a.csv --lambda 0:1:1e-9").starts_with("invalid value '0:1:1e-9' for --lambda"));
+        assert!(error("xval a.csv --k 1").starts_with("invalid value '1' for --k"));
+    }
+}
@@ -0,0 +1,30 @@
+// OXIGRAD LIBRARY
+// autograd engine (engine, sync_engine, tape, compile, dual for forward mode, Jacobians and Hessians
+// in functional), neural networks built on it (nn) and everything around their training: data loading
+// and generation, preprocessing, optimizers, training loop, cross validation, callbacks, history,
+// plots, persistence and experiment configuration
+mod oxigrad;
+
+pub use oxigrad::{
+    engine,
+    sync_engine,
+    nn,
+    utils,
+    xval,
+    data,
+    dataset,
+    preprocessing,
+    optim,
+    train,
+    callbacks,
+    history,
+    persist,
+    plot,
+    image,
+    config,
+    error,
+    dual,
+    functional,
+    tape,
+    compile,
+};
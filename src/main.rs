@@ -1,77 +1,363 @@
-// TODO: implement model export to some kind of file
-mod oxigrad;
+mod cli;
 
 use oxigrad::nn::Model;
-use oxigrad::utils::{mse, svm_maxmargin, l2, alpha};
+use oxigrad::engine::Value;
+use oxigrad::utils::{mse, alpha, accuracy, train_test_split, train_val_test_split};
 use oxigrad::data::{INP_DATASET, LBLS_DATASET};
-use rand::Rng;
-use crate::oxigrad::nn::Base;
-use crate::oxigrad::engine::Value;
-use crate::oxigrad::xval::{XVal, FloatingRange};
+use oxigrad::dataset::{Dataset, CsvOptions};
+use oxigrad::preprocessing::{Pipeline, Preprocessor, StandardScaler, Transformer};
+use oxigrad::persist::SavedModel;
+use oxigrad::callbacks::{CallbackList, EarlyStopping, TerminateOnNaN};
+use oxigrad::plot::{loss_curve, decision_boundary};
+use oxigrad::image::{history_svg, decision_boundary_svg, decision_boundary_ppm};
+use oxigrad::optim::{self, Sgd};
+use oxigrad::train::{fit, evaluate, loss_by_name, TrainOptions};
+use oxigrad::xval::{XVal, FloatingRange};
+use oxigrad::config::Experiment;
+use cli::{Command, TrainArgs, XValArgs, USAGE};
+use std::env;
+use std::fs;
+use std::process;
 
 fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let command = cli::parse(&args).unwrap_or_else(|e| {
+        eprintln!("error: {}\n\n{}", e, USAGE);
+        process::exit(2);
+    });
+
+    let result = match command {
+        Command::Demo { data } => demo(data),
+        Command::Run { experiment } => run(&experiment),
+        Command::Train(args) => train(args),
+        Command::Eval { model, data, loss } => eval(&model, &data, &loss),
+        Command::Predict { model, inputs } => predict(&model, &inputs),
+        Command::XVal(args) => xval(args),
+        Command::Help => {
+            println!("{}", USAGE);
+            Ok(())
+        }
+    };
+
+    if let Err(e) = result {
+        eprintln!("==> Error: {}", e);
+        process::exit(1);
+    }
+}
+
+fn load(path: &str) -> Result<Dataset, String> {
+    Dataset::from_csv(path, &CsvOptions::default()).map_err(|e| format!("loading {}: {}", path, e))
+}
+
+fn load_model(path: &str) -> Result<SavedModel, String> {
+    SavedModel::load(path).map_err(|e| format!("loading {}: {}", path, e))
+}
+
+// DEMO COMMAND
+// every step from data splitting to cross validation, training, plotting and saving
+fn demo(data: Option<String>) -> Result<(), String> {
+    // an optional CSV file replaces the preloaded dataset
+    // data generated with scikit-learn's make_moon method (n_samples=100, noise=0.1)
+    let ds = match data {
+        Some(path) => load(&path)?,
+        None => Dataset::from_rows(&INP_DATASET, &LBLS_DATASET).map_err(|e| e.to_string())?,
+    };
+
+    // held out data: the validation set monitors the training, the test set is only used at the end
+    let split = train_val_test_split(&ds.features, &ds.targets, 0.2, 0.2, 42, true).map_err(|e| e.to_string())?;
+    println!(
+        "==> Split data into train={}, validation={}, test={} samples",
+        split.train.1.len(),
+        split.validation.1.len(),
+        split.test.1.len(),
+    );
+
+    // features standardized with statistics fitted on the training set only
+    let mut pipeline = Pipeline::new(vec![Preprocessor::Standard(StandardScaler::new())]);
+    let train_x = &pipeline.fit_transform(&split.train.0);
+    let val_x = &pipeline.transform(&split.validation.0);
+    let test_x = &pipeline.transform(&split.test.0);
+    let (train_y, val_y, test_y) = (&split.train.1, &split.validation.1, &split.test.1);
+
     // WATCH OUT, changing the following hyperparameter (i.e. the NN architecture)
     // could require to change other hyperparameters as well like the alpha
     // and, in general, to do some tuning before training the resulting NN
+    // (experiment files, see the run command, allow doing so without editing this code)
     let arch = vec![16, 16, 1];
-    let m = Model::new(2, &arch);
+    let m = Model::new(ds.width(), &arch);
 
-    // cross validation to find best L2 lambda hyperparameter
-    // data generated with scikit-learn's make_moon method (n_samples=100, noise=0.1)
+    // cross validation (on the training set only) to find best L2 lambda hyperparameter
     let mut xv = XVal::new(
-        INP_DATASET.to_vec(),
-        LBLS_DATASET.to_vec(), 
+        train_x.clone(),
+        train_y.clone(),
         &arch, 
         FloatingRange::new(0.0, 0.01, 0.0005), 
         alpha, 
         mse,
         10,
-    );
-    let l2_lambda = xv.search_best_hyperpar();
+    ).map_err(|e| e.to_string())?;
+    let l2_lambda = xv.search_best_hyperpar().map_err(|e| e.to_string())?;
     println!("==> L2 lambda value={:.4}", l2_lambda);
 
-    println!("\n==> Choosing inputs and relative label from a preloaded dataset...");
-    let data_index = rand::thread_rng().gen_range(0, 100);
-    let inputs = INP_DATASET[data_index];
-    let label = LBLS_DATASET[data_index];
-    println!("==> Input values={:?}", inputs);
-    println!("==> Expected value={}", label);
+    // stop when the validation loss doesn't improve anymore (keeping the best parameters) or the loss diverges
+    let mut callbacks = CallbackList::new(vec![
+        Box::new(EarlyStopping::new("val_loss").patience(10).restore_best_weights(true)),
+        Box::new(TerminateOnNaN::default()),
+    ]);
 
     println!("\n==> Start training the model...");
-    let iterations = 50;
-    for pass in 0..iterations {
-        // prepping
-        m.zero_grad();
-
-        // forward pass
-        let preds = m.forward(&inputs);
-        let loss = mse(&preds, label);
-        // let loss = svm_maxmargin(&preds, label);
-
-        // L2 regularization
-        let reg = l2(&m.params(), Some(&Value::new(l2_lambda)));
-        let tot_loss = &loss + &reg;
-
-        // backward pass
-        tot_loss.backward(); 
-        for p in m.params().iter() {
-            p.core.borrow().data.set(p.get_data() - (alpha(pass, iterations) * p.get_grad()));
+    let options = TrainOptions {
+        epochs: 50,
+        loss: mse,
+        l2_lambda,
+        schedule: Some(Box::new(alpha)),
+        verbose: true,
+    };
+    let history = fit(&m, &mut Sgd::new(alpha(0, 50)), (train_x, train_y), Some((val_x, val_y)), &options, &mut callbacks)
+        .map_err(|e| e.to_string())?;
+
+    println!("\n==> Training loss");
+    print!("{}", loss_curve(&history.series("loss"), 60, 12));
+    if ds.width() == 2 {
+        println!("\n==> Decision boundary on the training set");
+        print!("{}", decision_boundary(&m, train_x, train_y, 60, 24));
+    }
+
+    let test_preds: Vec<Value> = test_x.iter().map(|x| m.forward(x)).collect();
+    println!("\n==> Test accuracy={:.0}%", accuracy(&test_preds, test_y) * 100.0);
+
+    // training history for external plotting tools
+    for (path, saved) in [
+        ("history.csv", history.to_csv("history.csv")),
+        ("history.jsonl", history.to_jsonl("history.jsonl")),
+    ] {
+        match saved {
+            Ok(()) => println!("==> History saved to {}", path),
+            Err(e) => eprintln!("==> Error saving history to {}: {}", path, e),
         }
+    }
 
-        println!(
-            "pass={}, alpha={:.3}, prediction={:.6}, reg={:.6}, loss={:.6}, tot_loss={:.6}", 
-            pass,
-            alpha(pass, iterations),
-            preds.get_data(),
-            reg.get_data(),
-            loss.get_data(),
-            tot_loss.get_data(),
-        );
-
-        // early exit (when results are good enough)
-        if loss.get_data() < 0.0001 {
-            break;
+    // plots as images, for reports and for comparing runs
+    let mut images = vec![
+        ("loss.svg", history_svg(&history, &["loss", "val_loss"], 640, 400).into_bytes()),
+    ];
+    if ds.width() == 2 {
+        images.push(("boundary.svg", decision_boundary_svg(&m, train_x, train_y, 480, 480, 80, 80).into_bytes()));
+        images.push(("boundary.ppm", decision_boundary_ppm(&m, train_x, train_y, 480, 480)));
+    }
+    for (path, bytes) in images {
+        match fs::write(path, bytes) {
+            Ok(()) => println!("==> Plot saved to {}", path),
+            Err(e) => eprintln!("==> Error saving plot to {}: {}", path, e),
         }
     }
+
+    // the fitted preprocessing is saved along with the model, so inference applies the same transformation
+    let model_path = "model.json";
+    match SavedModel::new(&m, &pipeline).save(model_path) {
+        Ok(()) => println!("==> Model saved to {}", model_path),
+        Err(e) => eprintln!("==> Error saving model to {}: {}", model_path, e),
+    }
     println!("==> DONE");
+
+    Ok(())
+}
+
+
+// RUN COMMAND
+// everything an experiment file describes, with all of the run's files written to its output directory
+fn run(path: &str) -> Result<(), String> {
+    let exp = Experiment::load(path).map_err(|e| format!("{}: {}", path, e))?;
+    let dir = exp.output_dir();
+    fs::create_dir_all(&dir).map_err(|e| format!("creating {}: {}", dir.display(), e))?;
+    let output = |file: &str| dir.join(file);
+
+    // echoed with every default filled in, so that the run can be reproduced
+    exp.save(output("experiment.json")).map_err(|e| format!("saving the experiment: {}", e))?;
+    println!("==> Running experiment '{}' into {}", exp.name, dir.display());
+
+    let ds = match &exp.dataset.path {
+        Some(p) => Dataset::from_csv(p, &exp.dataset.csv_options()).map_err(|e| format!("loading {}: {}", p, e))?,
+        None => Dataset::from_rows(&INP_DATASET, &LBLS_DATASET).map_err(|e| e.to_string())?,
+    };
+    let d = &exp.dataset;
+    let split = train_val_test_split(&ds.features, &ds.targets, d.validation, d.test, d.seed, d.stratified)
+        .map_err(|e| e.to_string())?;
+    println!(
+        "==> Split data into train={}, validation={}, test={} samples",
+        split.train.1.len(),
+        split.validation.1.len(),
+        split.test.1.len(),
+    );
+
+    let mut pipeline = Pipeline::new(exp.preprocessing.iter().map(|p| p.preprocessor()).collect());
+    let train_x = pipeline.fit_transform(&split.train.0);
+    let val_x = pipeline.transform(&split.validation.0);
+    let test_x = pipeline.transform(&split.test.0);
+    let (train_y, val_y, test_y) = (&split.train.1, &split.validation.1, &split.test.1);
+    let loss = loss_by_name(&exp.loss).expect("loss name already validated");
+
+    let mut l2_lambda = exp.regularizer.lambda();
+    if let Some(cv) = &exp.cv {
+        let (start, end, step) = cv.lambda;
+        // folds trained with the same settings as the final model
+        let optimizer = exp.optimizer.clone();
+        let mut xv = XVal::new(train_x.clone(), train_y.clone(), &exp.model.arch, FloatingRange::new(start, end, step), alpha, loss, cv.k)
+            .map_err(|e| e.to_string())?
+            .activations(&exp.model.activations())
+            .optimizer(move || optimizer.build())
+            .schedule(exp.scheduler.schedule(exp.optimizer.lr()))
+            .epochs(exp.epochs);
+        l2_lambda = xv.search_best_hyperpar().map_err(|e| e.to_string())?;
+        println!("==> L2 lambda value={:.4}", l2_lambda);
+    }
+
+    // preprocessing (e.g. one-hot encoding) may change the number of features
+    let width = train_x.first().map_or(ds.width(), |r| r.len());
+    let m = Model::try_with_activations(width, &exp.model.arch, &exp.model.activations()).map_err(|e| e.to_string())?;
+    let mut optimizer = exp.optimizer.build();
+    let mut callbacks = CallbackList::new(vec![Box::new(TerminateOnNaN::default())]);
+    if let Some(es) = &exp.early_stopping {
+        callbacks.push(Box::new(es.callback()));
+    }
+
+    println!("\n==> Start training the model...");
+    let options = TrainOptions {
+        epochs: exp.epochs,
+        loss,
+        l2_lambda,
+        schedule: exp.scheduler.schedule(exp.optimizer.lr()),
+        verbose: true,
+    };
+    let validation = if val_y.is_empty() { None } else { Some((&val_x[..], &val_y[..])) };
+    let history = fit(&m, optimizer.as_mut(), (&train_x, train_y), validation, &options, &mut callbacks)
+        .map_err(|e| e.to_string())?;
+
+    if !test_y.is_empty() {
+        let result = evaluate(&m, &test_x, test_y, loss);
+        println!("\n==> Test loss={:.6}, accuracy={:.0}%", result.loss, result.accuracy * 100.0);
+    }
+
+    let saved = [
+        ("model.json", SavedModel::new(&m, &pipeline).save(output("model.json"))),
+        ("history.csv", history.to_csv(output("history.csv"))),
+        ("history.jsonl", history.to_jsonl(output("history.jsonl"))),
+        ("loss.svg", fs::write(output("loss.svg"), history_svg(&history, &["loss", "val_loss"], 640, 400))),
+    ];
+    for (file, result) in saved {
+        result.map_err(|e| format!("saving {}: {}", output(file).display(), e))?;
+    }
+    println!("==> Model, history and plots saved to {}", dir.display());
+
+    Ok(())
+}
+
+// TRAIN COMMAND
+fn train(args: TrainArgs) -> Result<(), String> {
+    let ds = load(&args.data)?;
+    let ((train_x, train_y), (val_x, val_y)) = train_test_split(&ds.features, &ds.targets, args.validation, args.seed, true)
+        .map_err(|e| e.to_string())?;
+    println!("==> Split data into train={}, validation={} samples", train_y.len(), val_y.len());
+
+    let mut pipeline = Pipeline::new(vec![Preprocessor::Standard(StandardScaler::new())]);
+    let train_x = pipeline.fit_transform(&train_x);
+    let val_x = pipeline.transform(&val_x);
+    let validation = if val_y.is_empty() { None } else { Some((&val_x[..], &val_y[..])) };
+
+    let m = Model::new(ds.width(), &args.arch);
+    // names were checked while parsing the command line
+    let mut optimizer = optim::by_name(&args.optimizer, args.lr).expect("optimizer name already validated");
+    let loss = loss_by_name(&args.loss).expect("loss name already validated");
+
+    let mut callbacks = CallbackList::new(vec![Box::new(TerminateOnNaN::default())]);
+    if let Some(patience) = args.patience {
+        let monitor = if validation.is_some() { "val_loss" } else { "loss" };
+        callbacks.push(Box::new(EarlyStopping::new(monitor).patience(patience).restore_best_weights(true)));
+    }
+
+    println!("==> Training a {:?} model with {} (lr={}) on {} loss...", args.arch, args.optimizer, args.lr, args.loss);
+    let options = TrainOptions {
+        epochs: args.epochs,
+        loss,
+        l2_lambda: args.l2,
+        schedule: None,
+        verbose: true,
+    };
+    fit(&m, optimizer.as_mut(), (&train_x, &train_y), validation, &options, &mut callbacks)
+        .map_err(|e| e.to_string())?;
+
+    let result = evaluate(&m, &train_x, &train_y, loss);
+    println!("==> Training loss={:.6}, accuracy={:.0}%", result.loss, result.accuracy * 100.0);
+    if let Some((val_x, val_y)) = validation {
+        let result = evaluate(&m, val_x, val_y, loss);
+        println!("==> Validation loss={:.6}, accuracy={:.0}%", result.loss, result.accuracy * 100.0);
+    }
+
+    SavedModel::new(&m, &pipeline).save(&args.output).map_err(|e| format!("saving {}: {}", args.output, e))?;
+    println!("==> Model saved to {}", args.output);
+
+    Ok(())
+}
+
+// EVAL COMMAND
+fn eval(model: &str, data: &str, loss: &str) -> Result<(), String> {
+    let saved = load_model(model)?;
+    let ds = load(data)?;
+    if let Some(n) = saved.features().filter(|n| *n != ds.width()) {
+        return Err(format!("{} has {} features per sample, the model expects {}", data, ds.width(), n));
+    }
+    let x = saved.pipeline.transform(&ds.features);
+
+    let m = saved.model().map_err(|e| e.to_string())?;
+    let result = evaluate(&m, &x, &ds.targets, loss_by_name(loss).expect("loss name already validated"));
+    println!("samples={}, loss={:.6}, accuracy={:.0}%", ds.len(), result.loss, result.accuracy * 100.0);
+
+    Ok(())
+}
+
+// PREDICT COMMAND
+fn predict(model: &str, inputs: &[Vec<f64>]) -> Result<(), String> {
+    let saved = load_model(model)?;
+    let m = saved.model().map_err(|e| e.to_string())?;
+
+    for x in inputs.iter() {
+        let output = saved.predict(&m, x).map_err(|e| format!("input {:?}: {}", x, e))?;
+        match saved.pipeline.labels.as_ref().and_then(|l| l.decode(output)) {
+            Some(label) => println!("{:?} => {:.6} ({})", x, output, label),
+            None => println!("{:?} => {:.6}", x, output),
+        }
+    }
+
+    Ok(())
+}
+
+// XVAL COMMAND
+// the best L2 lambda of each architecture, then the architecture with the highest accuracy
+fn xval(args: XValArgs) -> Result<(), String> {
+    let ds = load(&args.data)?;
+    if ds.len() < args.k {
+        return Err(format!("{} has {} samples, less than the {} folds", args.data, ds.len(), args.k));
+    }
+    let x = Pipeline::new(vec![Preprocessor::Standard(StandardScaler::new())]).fit_transform(&ds.features);
+    let loss = loss_by_name(&args.loss).expect("loss name already validated");
+    let (start, end, step) = args.lambdas;
+
+    let mut best: Option<(&Vec<usize>, f64, f64)> = None;
+    for arch in args.archs.iter() {
+        println!("==> Architecture {:?}", arch);
+        let mut xv = XVal::new(x.clone(), ds.targets.clone(), arch, FloatingRange::new(start, end, step), alpha, loss, args.k)
+            .map_err(|e| e.to_string())?;
+        let lambda = xv.search_best_hyperpar().map_err(|e| e.to_string())?;
+        let score = xv.best_score().unwrap_or(0.0);
+
+        if best.is_none_or(|(_, _, s)| score > s) {
+            best = Some((arch, lambda, score));
+        }
+    }
+
+    if let Some((arch, lambda, score)) = best {
+        println!("==> Best configuration: arch={:?}, l2 lambda={:.4}, accuracy={:.0}%", arch, lambda, score * 100.0);
+    }
+
+    Ok(())
 }
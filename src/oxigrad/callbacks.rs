@@ -0,0 +1,481 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use serde::{Serialize, Deserialize};
+use super::engine::{Backend, Value};
+use super::nn::Base;
+
+// metrics reported by the training loop (e.g. "loss", "val_loss", "val_accuracy")
+pub type Logs = HashMap<String, f64>;
+
+// what the training loop should do after a callback has been invoked
+#[derive(Clone, Debug, PartialEq)]
+pub enum Control {
+    Continue,
+    Stop,
+    // stop and report the error to the caller of the training loop (see OxiError::Aborted)
+    Abort(String),
+}
+
+// whether the monitored metric improves when decreasing (e.g. a loss) or increasing (e.g. an accuracy)
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Mode {
+    Min,
+    Max,
+}
+
+impl Mode {
+    fn improved(&self, current: f64, best: Option<f64>, min_delta: f64) -> bool {
+        match (self, best) {
+            (_, None) => !current.is_nan(),
+            (Mode::Min, Some(b)) => current < b - min_delta,
+            (Mode::Max, Some(b)) => current > b + min_delta,
+        }
+    }
+}
+
+// CALLBACK TRAIT
+// hooks invoked by any training loop built on Model and Base::params (whatever the backend, e.g. a
+// SyncModel or a TapeModel), every one of them with a default implementation so that callbacks only
+// override the events they need
+pub trait Callback<V: Backend = Value> {
+    fn on_train_start(&mut self, _model: &dyn Base<V>) {}
+
+    fn on_train_end(&mut self, _model: &dyn Base<V>) {}
+
+    fn on_epoch_start(&mut self, _epoch: usize, _model: &dyn Base<V>) -> Control {
+        Control::Continue
+    }
+
+    fn on_epoch_end(&mut self, _epoch: usize, _logs: &Logs, _model: &dyn Base<V>) -> Control {
+        Control::Continue
+    }
+
+    fn on_batch_start(&mut self, _batch: usize, _model: &dyn Base<V>) -> Control {
+        Control::Continue
+    }
+
+    fn on_batch_end(&mut self, _batch: usize, _logs: &Logs, _model: &dyn Base<V>) -> Control {
+        Control::Continue
+    }
+}
+
+// CALLBACK LIST IMPLEMENTATION
+// dispatches every event to all of its callbacks, stopping if any of them asks to
+// (the first abort wins over stopping, which wins over continuing)
+pub struct CallbackList<V: Backend = Value> {
+    callbacks: Vec<Box<dyn Callback<V>>>,
+}
+
+impl<V: Backend> Default for CallbackList<V> {
+    fn default() -> Self {
+        CallbackList { callbacks: Vec::new() }
+    }
+}
+
+impl<V: Backend> CallbackList<V> {
+    pub fn new(callbacks: Vec<Box<dyn Callback<V>>>) -> Self {
+        CallbackList { callbacks }
+    }
+
+    pub fn push(&mut self, callback: Box<dyn Callback<V>>) {
+        self.callbacks.push(callback);
+    }
+
+    fn dispatch<F: FnMut(&mut Box<dyn Callback<V>>) -> Control>(&mut self, mut f: F) -> Control {
+        self.callbacks.iter_mut()
+            .map(&mut f)
+            .fold(Control::Continue, |c, r| match (&c, &r) {
+                (Control::Continue, _) | (Control::Stop, Control::Abort(_)) => r,
+                _ => c,
+            })
+    }
+}
+
+impl<V: Backend> Callback<V> for CallbackList<V> {
+    fn on_train_start(&mut self, model: &dyn Base<V>) {
+        self.callbacks.iter_mut().for_each(|c| c.on_train_start(model));
+    }
+
+    fn on_train_end(&mut self, model: &dyn Base<V>) {
+        self.callbacks.iter_mut().for_each(|c| c.on_train_end(model));
+    }
+
+    fn on_epoch_start(&mut self, epoch: usize, model: &dyn Base<V>) -> Control {
+        self.dispatch(|c| c.on_epoch_start(epoch, model))
+    }
+
+    fn on_epoch_end(&mut self, epoch: usize, logs: &Logs, model: &dyn Base<V>) -> Control {
+        self.dispatch(|c| c.on_epoch_end(epoch, logs, model))
+    }
+
+    fn on_batch_start(&mut self, batch: usize, model: &dyn Base<V>) -> Control {
+        self.dispatch(|c| c.on_batch_start(batch, model))
+    }
+
+    fn on_batch_end(&mut self, batch: usize, logs: &Logs, model: &dyn Base<V>) -> Control {
+        self.dispatch(|c| c.on_batch_end(batch, logs, model))
+    }
+}
+
+fn snapshot<V: Backend>(model: &dyn Base<V>) -> Vec<f64> {
+    model.params().iter().map(|p| p.get_data()).collect()
+}
+
+fn restore<V: Backend>(model: &dyn Base<V>, weights: &[f64]) {
+    for (p, w) in model.params().iter().zip(weights.iter()) {
+        p.set_data(*w);
+    }
+}
+
+// EARLY STOPPING IMPLEMENTATION
+// stops the training when the monitored metric hasn't improved by more than min_delta
+// for patience epochs, optionally putting back the parameters of the best epoch
+pub struct EarlyStopping {
+    monitor: String,
+    mode: Mode,
+    patience: usize,
+    min_delta: f64,
+    restore_best_weights: bool,
+    best: Option<f64>,
+    best_epoch: Option<usize>,
+    best_weights: Vec<f64>,
+    wait: usize,
+    stopped_epoch: Option<usize>,
+}
+
+impl EarlyStopping {
+    pub fn new(monitor: &str) -> Self {
+        EarlyStopping {
+            monitor: monitor.to_string(),
+            mode: Mode::Min,
+            patience: 5,
+            min_delta: 0.0,
+            restore_best_weights: false,
+            best: None,
+            best_epoch: None,
+            best_weights: Vec::new(),
+            wait: 0,
+            stopped_epoch: None,
+        }
+    }
+
+    pub fn mode(mut self, mode: Mode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    pub fn patience(mut self, patience: usize) -> Self {
+        self.patience = patience;
+        self
+    }
+
+    pub fn min_delta(mut self, min_delta: f64) -> Self {
+        self.min_delta = min_delta.abs();
+        self
+    }
+
+    pub fn restore_best_weights(mut self, restore: bool) -> Self {
+        self.restore_best_weights = restore;
+        self
+    }
+
+    pub fn best(&self) -> Option<f64> {
+        self.best
+    }
+
+    pub fn best_epoch(&self) -> Option<usize> {
+        self.best_epoch
+    }
+
+    pub fn stopped_epoch(&self) -> Option<usize> {
+        self.stopped_epoch
+    }
+}
+
+impl<V: Backend> Callback<V> for EarlyStopping {
+    fn on_train_start(&mut self, _model: &dyn Base<V>) {
+        self.best = None;
+        self.best_epoch = None;
+        self.best_weights.clear();
+        self.wait = 0;
+        self.stopped_epoch = None;
+    }
+
+    fn on_train_end(&mut self, model: &dyn Base<V>) {
+        // training may also end without being stopped, the best weights are still wanted then
+        if self.restore_best_weights && self.stopped_epoch.is_none() && !self.best_weights.is_empty() {
+            restore(model, &self.best_weights);
+        }
+    }
+
+    fn on_epoch_end(&mut self, epoch: usize, logs: &Logs, model: &dyn Base<V>) -> Control {
+        let current = match logs.get(&self.monitor) {
+            Some(v) => *v,
+            None => return Control::Continue,
+        };
+
+        if self.mode.improved(current, self.best, self.min_delta) {
+            self.best = Some(current);
+            self.best_epoch = Some(epoch);
+            self.wait = 0;
+            if self.restore_best_weights {
+                self.best_weights = snapshot(model);
+            }
+            return Control::Continue;
+        }
+
+        self.wait += 1;
+        if self.wait < self.patience {
+            return Control::Continue;
+        }
+
+        self.stopped_epoch = Some(epoch);
+        if self.restore_best_weights && !self.best_weights.is_empty() {
+            restore(model, &self.best_weights);
+        }
+        Control::Stop
+    }
+}
+
+// MODEL CHECKPOINT IMPLEMENTATION
+// parameters saved to a JSON file (in the same order as Base::params) at the end of every epoch,
+// or only when the monitored metric improves
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Checkpoint {
+    pub epoch: usize,
+    pub monitor: String,
+    pub value: Option<f64>,
+    pub params: Vec<f64>,
+}
+
+pub struct ModelCheckpoint {
+    path: PathBuf,
+    monitor: String,
+    mode: Mode,
+    save_best_only: bool,
+    best: Option<f64>,
+}
+
+impl ModelCheckpoint {
+    pub fn new<P: AsRef<Path>>(path: P, monitor: &str) -> Self {
+        ModelCheckpoint {
+            path: path.as_ref().to_path_buf(),
+            monitor: monitor.to_string(),
+            mode: Mode::Min,
+            save_best_only: true,
+            best: None,
+        }
+    }
+
+    pub fn mode(mut self, mode: Mode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    pub fn save_best_only(mut self, save_best_only: bool) -> Self {
+        self.save_best_only = save_best_only;
+        self
+    }
+
+    // put the parameters of a checkpoint back into the model
+    pub fn restore<P: AsRef<Path>, V: Backend>(path: P, model: &dyn Base<V>) -> io::Result<Checkpoint> {
+        let checkpoint: Checkpoint = serde_json::from_str(&fs::read_to_string(path)?)?;
+        if checkpoint.params.len() != model.params().len() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("checkpoint has {} parameters, model has {}", checkpoint.params.len(), model.params().len()),
+            ));
+        }
+
+        restore(model, &checkpoint.params);
+        Ok(checkpoint)
+    }
+}
+
+impl<V: Backend> Callback<V> for ModelCheckpoint {
+    // the best value is the one of the current training (e.g. the same checkpoint reused for each fold)
+    fn on_train_start(&mut self, _model: &dyn Base<V>) {
+        self.best = None;
+    }
+
+    fn on_epoch_end(&mut self, epoch: usize, logs: &Logs, model: &dyn Base<V>) -> Control {
+        let current = logs.get(&self.monitor).cloned();
+
+        if self.save_best_only {
+            match current {
+                Some(c) if self.mode.improved(c, self.best, 0.0) => self.best = Some(c),
+                _ => return Control::Continue,
+            }
+        }
+
+        let checkpoint = Checkpoint {
+            epoch,
+            monitor: self.monitor.clone(),
+            value: current,
+            params: snapshot(model),
+        };
+        // the training loop reports the failure, the model keeps the training done so far
+        let saved = serde_json::to_string(&checkpoint)
+            .map_err(io::Error::from)
+            .and_then(|json| fs::write(&self.path, json));
+        match saved {
+            Ok(()) => Control::Continue,
+            Err(e) => Control::Abort(format!("saving checkpoint to {}: {}", self.path.display(), e)),
+        }
+    }
+}
+
+// TERMINATE ON NAN IMPLEMENTATION
+// stops the training as soon as the monitored loss is NaN or infinite
+pub struct TerminateOnNaN {
+    monitor: String,
+}
+
+impl TerminateOnNaN {
+    pub fn new(monitor: &str) -> Self {
+        TerminateOnNaN {
+            monitor: monitor.to_string(),
+        }
+    }
+
+    fn check(&self, logs: &Logs) -> Control {
+        match logs.get(&self.monitor) {
+            Some(v) if !v.is_finite() => Control::Stop,
+            _ => Control::Continue,
+        }
+    }
+}
+
+impl Default for TerminateOnNaN {
+    fn default() -> Self {
+        TerminateOnNaN::new("loss")
+    }
+}
+
+impl<V: Backend> Callback<V> for TerminateOnNaN {
+    fn on_epoch_end(&mut self, _epoch: usize, logs: &Logs, _model: &dyn Base<V>) -> Control {
+        self.check(logs)
+    }
+
+    fn on_batch_end(&mut self, _batch: usize, logs: &Logs, _model: &dyn Base<V>) -> Control {
+        self.check(logs)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use super::super::nn::{Model, SyncModel};
+    use super::super::sync_engine::SyncValue;
+
+    fn logs(key: &str, value: f64) -> Logs {
+        Logs::from([(key.to_string(), value)])
+    }
+
+    #[test]
+    fn test_early_stopping() {
+        let m = Model::new(2, &[2, 1]);
+        let mut es = EarlyStopping::new("val_loss").patience(2).min_delta(0.01).restore_best_weights(true);
+        es.on_train_start(&m);
+
+        let initial = snapshot(&m);
+        let losses = [1.0, 0.5, 0.495, 0.6, 0.4];
+        let mut stopped = None;
+        for (epoch, loss) in losses.iter().enumerate() {
+            // parameters change at every epoch
+            for p in m.params() {
+                p.set_data(p.get_data() + 1.0);
+            }
+
+            if es.on_epoch_end(epoch, &logs("val_loss", *loss), &m) == Control::Stop {
+                stopped = Some(epoch);
+                break;
+            }
+        }
+
+        // 0.495 is not an improvement given min_delta, so patience runs out at epoch 3
+        assert_eq!(stopped, Some(3));
+        assert_eq!(es.best(), Some(0.5));
+        assert_eq!(es.best_epoch(), Some(1));
+        assert_eq!(snapshot(&m), initial.iter().map(|w| w + 2.0).collect::<Vec<f64>>());
+    }
+
+    #[test]
+    fn test_early_stopping_max_mode() {
+        let m = Model::new(2, &[1]);
+        let mut es = EarlyStopping::new("val_accuracy").mode(Mode::Max).patience(1);
+
+        assert_eq!(es.on_epoch_end(0, &logs("val_accuracy", 0.6), &m), Control::Continue);
+        assert_eq!(es.on_epoch_end(1, &logs("val_accuracy", 0.7), &m), Control::Continue);
+        // metrics not being monitored are ignored
+        assert_eq!(es.on_epoch_end(2, &logs("loss", 9.0), &m), Control::Continue);
+        assert_eq!(es.on_epoch_end(3, &logs("val_accuracy", 0.7), &m), Control::Stop);
+        assert_eq!(es.stopped_epoch(), Some(3));
+    }
+
+    #[test]
+    fn test_model_checkpoint() {
+        let path = std::env::temp_dir().join("oxigrad_test_model_checkpoint.json");
+        let m = Model::new(2, &[3, 1]);
+        let mut mc = ModelCheckpoint::new(&path, "val_loss");
+
+        mc.on_epoch_end(0, &logs("val_loss", 0.5), &m);
+        let saved = snapshot(&m);
+
+        // worse epochs aren't saved
+        for p in m.params() {
+            p.set_data(0.0);
+        }
+        mc.on_epoch_end(1, &logs("val_loss", 0.8), &m);
+
+        let checkpoint = ModelCheckpoint::restore(&path, &m).unwrap();
+        assert_eq!(checkpoint.epoch, 0);
+        assert_eq!(checkpoint.value, Some(0.5));
+        assert_eq!(snapshot(&m), saved);
+
+        // a new training starts from no best value
+        Callback::<Value>::on_train_start(&mut mc, &m);
+        mc.on_epoch_end(0, &logs("val_loss", 0.9), &m);
+        let checkpoint = ModelCheckpoint::restore(&path, &m).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(checkpoint.value, Some(0.9));
+
+        // failures abort the training instead of going unnoticed
+        let mut mc = ModelCheckpoint::new(std::env::temp_dir().join("oxigrad_missing_dir").join("c.json"), "val_loss");
+        assert!(matches!(mc.on_epoch_end(0, &logs("val_loss", 0.5), &m), Control::Abort(_)));
+    }
+
+    #[test]
+    fn test_terminate_on_nan() {
+        let m = Model::new(2, &[1]);
+        let mut callbacks = CallbackList::new(vec![
+            Box::new(TerminateOnNaN::default()),
+            Box::new(EarlyStopping::new("val_loss")),
+        ]);
+
+        assert_eq!(callbacks.on_batch_end(0, &logs("loss", 0.3), &m), Control::Continue);
+        assert_eq!(callbacks.on_batch_end(1, &logs("loss", f64::INFINITY), &m), Control::Stop);
+        assert_eq!(callbacks.on_epoch_end(0, &logs("loss", f64::NAN), &m), Control::Stop);
+    }
+
+    #[test]
+    fn test_other_backends() {
+        let m = SyncModel::with_backend(2, &[2, 1]);
+        let mut callbacks: CallbackList<SyncValue> = CallbackList::new(vec![
+            Box::new(EarlyStopping::new("loss").patience(1).restore_best_weights(true)),
+            Box::new(ModelCheckpoint::new(std::env::temp_dir().join("oxigrad_missing_dir").join("c.json"), "loss")),
+        ]);
+        callbacks.on_train_start(&m);
+        let initial = snapshot(&m);
+
+        // aborting wins over stopping
+        assert!(matches!(callbacks.on_epoch_end(0, &logs("loss", 0.5), &m), Control::Abort(_)));
+        for p in m.params() {
+            p.set_data(0.0);
+        }
+        callbacks.on_train_end(&m);
+        assert_eq!(snapshot(&m), initial);
+    }
+}
@@ -0,0 +1,404 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt::Display;
+use super::engine::{Operation, Value};
+use super::error::OxiError;
+
+// COMPILED GRAPH IMPLEMENTATION
+// a graph built once and replayed: new inputs are written into its placeholder leaves and the data of
+// the other nodes is recomputed in place, in the topological order found when compiling, so forward
+// and backward passes don't allocate nodes or closures nor sort the graph again
+// (leaves other than the placeholders, e.g. the parameters of a model, are read as they are at each pass,
+// so a leaf made by Value::detach while tracing is a constant holding its data of that time)
+pub struct CompiledGraph {
+    inputs: Vec<Value>,
+    output: Value,
+    order: Vec<Value>,
+    leaves: Vec<Value>,
+}
+
+impl CompiledGraph {
+    // every node the output depends on must have been built by the operations on Values
+    pub fn compile(output: &Value, inputs: &[Value]) -> Result<Self, OxiError> {
+        let (order, leaves) = output.graph()?;
+        for v in order.iter() {
+            v.recompute()?;
+        }
+
+        Ok(CompiledGraph {
+            inputs: inputs.to_vec(),
+            output: output.clone(),
+            order,
+            leaves,
+        })
+    }
+
+    // builds the graph of f on as many placeholders as the given inputs, holding their data
+    pub fn trace<F>(inputs: &[f64], f: F) -> Result<Self, OxiError>
+        where F: FnOnce(&[Value]) -> Value
+    {
+        let placeholders: Vec<Value> = inputs.iter().map(|x| Value::new(*x)).collect();
+        let output = f(&placeholders);
+
+        CompiledGraph::compile(&output, &placeholders)
+    }
+
+    // sets the placeholders and recomputes the graph, returning the output's data
+    pub fn forward(&self, inputs: &[f64]) -> Result<f64, OxiError> {
+        if inputs.len() != self.inputs.len() {
+            return Err(OxiError::DimensionMismatch { expected: self.inputs.len(), found: inputs.len() });
+        }
+        for (p, x) in self.inputs.iter().zip(inputs) {
+            p.set_data(*x);
+        }
+        for v in self.order.iter() {
+            v.recompute().map_err(|e| self.output.locate(v, e))?;
+        }
+
+        Ok(self.output.get_data())
+    }
+
+    // same as backward on the output with BackwardOptions::zero_grad, i.e. intermediate grads are reset
+    // while leaves (e.g. parameters) accumulate, hooks included
+    pub fn backward(&self) -> Result<(), OxiError> {
+        for v in self.order.iter() {
+            v.set_grad(0.0);
+        }
+
+        self.output.sweep(&self.order, &self.leaves)
+    }
+
+    // a new graph computing the same output on the same inputs, where the leaves made by Value::constant
+    // (literals, e.g. of the operators taking an f64, and Value::detach) are treated as constants unless
+    // they're among the inputs and the given parameters or have hooks, any other leaf being a parameter:
+    // subexpressions of constants are folded into new constants, additions of 0 and multiplications by 1
+    // are removed and a + b * -1 (i.e. the Sub operator) is fused into a single subtraction
+    #[allow(clippy::mutable_key_type)]
+    pub fn simplify(&self, params: &[Value]) -> Result<(CompiledGraph, SimplifyReport), OxiError> {
+        let variables: HashSet<Value> = self.inputs.iter().chain(params).cloned().collect();
+        let mut constants: HashSet<Value> = HashSet::new();
+        let mut mapped: HashMap<Value, Value> = HashMap::new();
+        let mut report = SimplifyReport { before: count_nodes(&self.output), ..SimplifyReport::default() };
+
+        for node in self.order.iter() {
+            let children: Vec<Value> = node.core.borrow().children.as_deref().unwrap_or_default()
+                .iter()
+                .map(|c| match mapped.get(c) {
+                    Some(m) => m.clone(),
+                    None => {
+                        if c.is_constant() && !variables.contains(c) && !c.has_hooks() {
+                            constants.insert(c.clone());
+                        }
+                        c.clone()
+                    },
+                })
+                .collect();
+
+            let new = if children.iter().all(|c| constants.contains(c)) {
+                report.folded += 1;
+                let c = Value::constant(node.get_data());
+                constants.insert(c.clone());
+                c
+            } else {
+                let is = |v: &Value, x: f64| constants.contains(v) && v.get_data() == x;
+                match node.op() {
+                    Some(Operation::Addition) => {
+                        let (a, b) = (&children[0], &children[1]);
+                        if is(a, 0.0) || is(b, 0.0) {
+                            report.identities += 1;
+                            if is(a, 0.0) { b.clone() } else { a.clone() }
+                        } else if let Some(n) = negated(b, &constants) {
+                            report.fused += 1;
+                            a.subtraction(&n)
+                        } else if let Some(n) = negated(a, &constants) {
+                            report.fused += 1;
+                            b.subtraction(&n)
+                        } else {
+                            a + b
+                        }
+                    },
+                    Some(Operation::Multiplication) => {
+                        let (a, b) = (&children[0], &children[1]);
+                        if is(a, 1.0) || is(b, 1.0) {
+                            report.identities += 1;
+                            if is(a, 1.0) { b.clone() } else { a.clone() }
+                        } else {
+                            a * b
+                        }
+                    },
+                    Some(Operation::Subtraction) => children[0].subtraction(&children[1]),
+                    Some(Operation::Power) => match node.exponent() {
+                        Some(exp) => children[0].power(exp),
+                        None => return Err(OxiError::NotReplayable { op: node.op() }),
+                    },
+                    Some(Operation::ReLU) => children[0].relu(),
+                    Some(Operation::StopGradient) => children[0].stop_gradient(),
+                    Some(Operation::Custom) => match node.custom_op() {
+                        Some(custom) => Value::custom(custom, &children),
+                        None => return Err(OxiError::NotReplayable { op: node.op() }),
+                    },
+                    op => return Err(OxiError::NotReplayable { op }),
+                }
+            };
+            mapped.insert(node.clone(), new);
+        }
+
+        let output = mapped.get(&self.output).cloned().unwrap_or_else(|| self.output.clone());
+        report.after = count_nodes(&output);
+
+        Ok((CompiledGraph::compile(&output, &self.inputs)?, report))
+    }
+
+    pub fn inputs(&self) -> &[Value] {
+        &self.inputs
+    }
+
+    pub fn output(&self) -> &Value {
+        &self.output
+    }
+
+    // number of nodes recomputed at each pass
+    pub fn len(&self) -> usize {
+        self.order.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.order.is_empty()
+    }
+}
+
+// SIMPLIFICATION REPORT
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct SimplifyReport {
+    // nodes reachable from the output, leaves included
+    pub before: usize,
+    pub after: usize,
+    pub folded: usize,
+    pub identities: usize,
+    pub fused: usize,
+}
+
+impl SimplifyReport {
+    pub fn removed(&self) -> usize {
+        self.before.saturating_sub(self.after)
+    }
+}
+
+impl Display for SimplifyReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "nodes={} -> {} (-{:.1}%), folded={}, identities={}, fused={}",
+            self.before,
+            self.after,
+            self.removed() as f64 / self.before.max(1) as f64 * 100.0,
+            self.folded,
+            self.identities,
+            self.fused,
+        )
+    }
+}
+
+// the x of x * -1 (or -1 * x), as built by the Neg operator
+#[allow(clippy::mutable_key_type)]
+fn negated(v: &Value, constants: &HashSet<Value>) -> Option<Value> {
+    if !matches!(v.op(), Some(Operation::Multiplication)) {
+        return None;
+    }
+    let core = v.core.borrow();
+    let children = core.children.as_ref()?;
+    let minus_one = |c: &Value| constants.contains(c) && c.get_data() == -1.0;
+
+    match (children.first(), children.get(1)) {
+        (Some(x), Some(c)) if minus_one(c) => Some(x.clone()),
+        (Some(c), Some(x)) if minus_one(c) => Some(x.clone()),
+        _ => None,
+    }
+}
+
+#[allow(clippy::mutable_key_type)]
+fn count_nodes(output: &Value) -> usize {
+    let mut visited = HashSet::new();
+    let mut stack = vec![output.clone()];
+
+    while let Some(node) = stack.pop() {
+        if visited.insert(node.clone()) {
+            if let Some(children) = node.core.borrow().children.as_ref() {
+                stack.extend(children.iter().cloned());
+            }
+        }
+    }
+
+    visited.len()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use super::super::engine::{detect_anomaly, Operation};
+    use super::super::error::Pass;
+    use super::super::nn::{Base, Model};
+
+    #[test]
+    fn test_replay() {
+        let w = Value::new(0.5);
+        let g = CompiledGraph::trace(&[1.0, 2.0], |x| {
+            (&(&(&x[0] * &w) + &x[1].power(2.0)) / &x[1]).relu()
+        }).unwrap();
+        assert_eq!(g.output().get_data(), 2.25);
+
+        // (3w + 16) / 4, with d/dw = 3/4 and d/dx0 = w/4
+        assert_eq!(g.forward(&[3.0, 4.0]).unwrap(), 4.375);
+        g.backward().unwrap();
+        assert_eq!((w.get_grad(), g.inputs()[0].get_grad()), (0.75, 0.125));
+
+        // leaves accumulate, intermediate nodes don't
+        g.backward().unwrap();
+        assert_eq!(w.get_grad(), 1.5);
+
+        // parameters are read at each pass
+        w.set_data(-100.0);
+        assert_eq!(g.forward(&[3.0, 4.0]).unwrap(), 0.0);
+        assert!(matches!(g.forward(&[1.0]), Err(OxiError::DimensionMismatch { expected: 2, found: 1 })));
+    }
+
+    #[test]
+    fn test_anomaly() {
+        let g = CompiledGraph::trace(&[1.0], |x| &x[0].power(-1.0) + &Value::new(1.0)).unwrap();
+        let _guard = detect_anomaly();
+
+        match g.forward(&[0.0]) {
+            Err(OxiError::Anomaly(a)) => {
+                assert_eq!((a.pass, a.op.as_str(), a.inputs), (Pass::Forward, "Power", vec![0.0]));
+                assert_eq!(a.path, vec!["Addition", "Power"]);
+            },
+            r => panic!("expected an anomaly, got {:?}", r),
+        }
+        assert_eq!(g.forward(&[2.0]).unwrap(), 1.5);
+    }
+
+    #[test]
+    fn test_not_replayable() {
+        let a = Value::new(1.0);
+        let b = Value::new((2.0, Some(Operation::Addition), Some(vec![a.clone()])));
+
+        assert!(matches!(CompiledGraph::compile(&b, &[a]), Err(OxiError::NotReplayable { .. })));
+    }
+
+    #[test]
+    fn test_model() {
+        let m = Model::new(2, &[4, 4, 1]);
+        let g = CompiledGraph::trace(&[0.0, 0.0, 0.0], |x| {
+            (&m.forward_nodes(&x[..2]) - &x[2]).power(2.0)
+        }).unwrap();
+
+        for (x, y) in [([0.5, -1.0], 1.0), ([2.0, 0.3], -1.0)] {
+            m.zero_grad();
+            let loss = g.forward(&[x[0], x[1], y]).unwrap();
+            g.backward().unwrap();
+            let grads: Vec<f64> = m.params().iter().map(|p| p.get_grad()).collect();
+
+            m.zero_grad();
+            let expected = (&m.forward(&x) - &Value::new(y)).power(2.0);
+            expected.backward();
+
+            assert_eq!(loss, expected.get_data());
+            assert_eq!(grads, m.params().iter().map(|p| p.get_grad()).collect::<Vec<f64>>());
+        }
+    }
+
+    #[test]
+    fn test_simplify() {
+        let w = Value::new(0.5);
+        let g = CompiledGraph::trace(&[1.0, 2.0], |x| {
+            // (x0 * w - x1) + 0 * 1 + (2 * 3) * x1 / 4 + sum of [x0 * 1]
+            let c = Value::constant;
+            let scale = &(&c(2.0) * &c(3.0)) / &c(4.0);
+            let diff = &(&x[0] * &w) - &x[1];
+            let sum = [&x[0] * &c(1.0)].iter().sum::<Value>();
+            &(&(&(&diff + &(&c(0.0) * &c(1.0))) + &(&scale * &x[1])) + &sum) * &c(1.0)
+        }).unwrap();
+        let (s, report) = g.simplify(std::slice::from_ref(&w)).unwrap();
+
+        assert!(report.after < report.before);
+        assert_eq!(report.removed(), report.before - report.after);
+        assert_eq!(report.fused, 1);
+        assert!(report.folded >= 3);
+        assert!(report.identities >= 3);
+        assert!(report.to_string().starts_with(&format!("nodes={} -> {}", report.before, report.after)));
+        assert!(s.len() < g.len());
+
+        // same results on new inputs, for the data and the grads of the parameters and of the inputs
+        for x in [[3.0, 4.0], [-1.0, 0.25]] {
+            assert_eq!(s.forward(&x).unwrap(), g.forward(&x).unwrap());
+            // both graphs share the inputs and the parameter, whose grads accumulate
+            let reset = || [&w, &g.inputs()[0], &g.inputs()[1]].iter().for_each(|v| v.set_grad(0.0));
+            reset();
+            g.backward().unwrap();
+            let expected = (w.get_grad(), g.inputs()[0].get_grad(), g.inputs()[1].get_grad());
+            reset();
+            s.backward().unwrap();
+            assert_eq!((w.get_grad(), s.inputs()[0].get_grad(), s.inputs()[1].get_grad()), expected);
+        }
+    }
+
+    #[test]
+    fn test_simplify_unlisted_params() {
+        // leaves that aren't constants aren't folded even if they aren't among the parameters, whatever
+        // their gradient (e.g. 0 after zero_grad), nor are constants with hooks
+        let (trained, zeroed, hooked) = (Value::new(2.0), Value::new(5.0), Value::constant(3.0));
+        trained.set_grad(0.5);
+        hooked.register_hook(|g| g);
+        let g = CompiledGraph::trace(&[1.0], |x| {
+            let c = Value::constant;
+            &(&(&x[0] * &(&trained * &c(3.0))) + &(&hooked * &c(2.0))) + &(&zeroed * &c(2.0))
+        }).unwrap();
+        let (s, report) = g.simplify(&[]).unwrap();
+        assert_eq!(report.folded, 0);
+
+        trained.set_data(-1.0);
+        zeroed.set_data(1.0);
+        hooked.set_data(4.0);
+        assert_eq!(s.forward(&[2.0]).unwrap(), g.forward(&[2.0]).unwrap());
+        assert_eq!(s.forward(&[2.0]).unwrap(), 4.0);
+
+        // while a detached leaf is
+        let w = Value::new(2.0);
+        let g = CompiledGraph::trace(&[1.0], |x| &x[0] * &(&w.detach() * &Value::constant(3.0))).unwrap();
+        assert_eq!(g.simplify(&[]).unwrap().1.folded, 1);
+    }
+
+    #[test]
+    fn test_simplify_model() {
+        let m = Model::new(2, &[8, 8, 1]);
+        let g = CompiledGraph::trace(&[0.0, 0.0, 0.0], |x| {
+            (&m.forward_nodes(&x[..2]) - &x[2]).power(2.0)
+        }).unwrap();
+        let (s, report) = g.simplify(&m.params()).unwrap();
+
+        // the 0 each neuron's dot product starts from, and the label's negation (both with their constants)
+        assert_eq!(report.identities, 17);
+        assert_eq!(report.fused, 1);
+        assert_eq!(report.removed(), 17 * 2 + 2);
+        assert_eq!(s.len(), g.len() - 17 - 1);
+        for x in [[0.5, -1.0, 1.0], [2.0, 0.3, -1.0]] {
+            assert_eq!(s.forward(&x).unwrap(), g.forward(&x).unwrap());
+        }
+    }
+
+    #[test]
+    fn test_hooks() {
+        let w = Value::new(2.0);
+        let g = CompiledGraph::trace(&[3.0], |x| &(&w * &x[0]) * &w).unwrap();
+
+        // the hook on the parameter fires on each replay, with that pass' gradient only
+        let seen = std::rc::Rc::new(std::cell::RefCell::new(vec![]));
+        let log = seen.clone();
+        w.register_hook(move |grad| { log.borrow_mut().push(grad); grad.min(5.0) });
+        g.backward().unwrap();
+        g.forward(&[1.0]).unwrap();
+        g.backward().unwrap();
+
+        assert_eq!(*seen.borrow(), vec![12.0, 4.0]);
+        assert_eq!(w.get_grad(), 5.0 + 4.0);
+    }
+}
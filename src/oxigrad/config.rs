@@ -0,0 +1,563 @@
+use std::fmt::Display;
+use std::fs;
+use std::path::{Path, PathBuf};
+use serde::{Serialize, Deserialize};
+use super::callbacks::{EarlyStopping, Mode};
+use super::dataset::{Column, CsvOptions};
+use super::nn::Activation;
+use super::optim::{Optimizer, Sgd, Momentum, Adam};
+use super::preprocessing::{Preprocessor, StandardScaler, MinMaxScaler, RobustScaler, OneHotEncoder};
+use super::train::{Schedule, LOSSES};
+
+// most L2 lambdas tried by a cross validation (each one training k models)
+pub const MAX_LAMBDAS: f64 = 1000.0;
+
+// EXPERIMENT CONFIGURATION ERRORS
+// a value that's well formed but can't be used, with the path of its field (e.g. "model.arch[1]")
+#[derive(Clone, Debug, PartialEq)]
+pub struct Problem {
+    pub field: String,
+    pub message: String,
+}
+
+#[derive(Debug)]
+pub enum ConfigError {
+    Io(std::io::Error),
+    // malformed JSON, unknown fields or wrong types (with line and column)
+    Parse(serde_json::Error),
+    Invalid(Vec<Problem>),
+}
+
+impl Display for ConfigError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConfigError::Io(e) => write!(f, "cannot read experiment file: {}", e),
+            ConfigError::Parse(e) => write!(f, "invalid experiment file: {}", e),
+            ConfigError::Invalid(problems) => {
+                write!(f, "invalid experiment configuration")?;
+                for p in problems.iter() {
+                    write!(f, "\n  {}: {}", p.field, p.message)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+// DEFAULT VALUES
+fn default_delimiter() -> char { ',' }
+fn default_ratio() -> f64 { 0.2 }
+fn default_seed() -> u64 { 42 }
+fn default_true() -> bool { true }
+fn default_range() -> (f64, f64) { (0.0, 1.0) }
+fn default_loss() -> String { String::from("mse") }
+fn default_epochs() -> usize { 50 }
+fn default_beta() -> f64 { 0.9 }
+fn default_beta2() -> f64 { 0.999 }
+fn default_eps() -> f64 { 1e-8 }
+fn default_monitor() -> String { String::from("val_loss") }
+fn default_patience() -> usize { 5 }
+fn default_k() -> usize { 10 }
+
+// DATASET SECTION
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DatasetConfig {
+    // CSV file, the preloaded moons dataset if missing
+    pub path: Option<String>,
+    #[serde(default = "default_delimiter")]
+    pub delimiter: char,
+    pub header: Option<bool>,
+    pub features: Option<Vec<Column>>,
+    pub label: Option<Column>,
+    #[serde(default = "default_ratio")]
+    pub validation: f64,
+    #[serde(default = "default_ratio")]
+    pub test: f64,
+    #[serde(default = "default_seed")]
+    pub seed: u64,
+    #[serde(default = "default_true")]
+    pub stratified: bool,
+}
+
+impl Default for DatasetConfig {
+    fn default() -> Self {
+        DatasetConfig {
+            path: None,
+            delimiter: default_delimiter(),
+            header: None,
+            features: None,
+            label: None,
+            validation: default_ratio(),
+            test: default_ratio(),
+            seed: default_seed(),
+            stratified: true,
+        }
+    }
+}
+
+impl DatasetConfig {
+    pub fn csv_options(&self) -> CsvOptions {
+        CsvOptions {
+            delimiter: self.delimiter,
+            header: self.header,
+            features: self.features.clone(),
+            label: self.label.clone(),
+        }
+    }
+}
+
+// PREPROCESSING SECTION
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+pub enum PreprocessingConfig {
+    Standard,
+    MinMax {
+        #[serde(default = "default_range")]
+        range: (f64, f64),
+    },
+    Robust,
+    OneHot {
+        columns: Vec<usize>,
+    },
+}
+
+impl PreprocessingConfig {
+    pub fn preprocessor(&self) -> Preprocessor {
+        match self {
+            PreprocessingConfig::Standard => Preprocessor::Standard(StandardScaler::new()),
+            PreprocessingConfig::MinMax { range } => Preprocessor::MinMax(MinMaxScaler::new(range.0, range.1)),
+            PreprocessingConfig::Robust => Preprocessor::Robust(RobustScaler::new()),
+            PreprocessingConfig::OneHot { columns } => Preprocessor::OneHot(OneHotEncoder::new(columns.clone())),
+        }
+    }
+}
+
+// MODEL SECTION
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ModelConfig {
+    pub arch: Vec<usize>,
+    // one per layer, ReLU on the hidden layers and a linear output if missing
+    pub activations: Option<Vec<Activation>>,
+}
+
+impl ModelConfig {
+    pub fn activations(&self) -> Vec<Activation> {
+        self.activations.clone().unwrap_or_else(|| Activation::defaults(self.arch.len()))
+    }
+}
+
+// REGULARIZER SECTION
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase", deny_unknown_fields)]
+pub enum RegularizerConfig {
+    #[default]
+    None,
+    L2 {
+        lambda: f64,
+    },
+}
+
+impl RegularizerConfig {
+    pub fn lambda(&self) -> f64 {
+        match self {
+            RegularizerConfig::None => 0.0,
+            RegularizerConfig::L2 { lambda } => *lambda,
+        }
+    }
+}
+
+// OPTIMIZER SECTION
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase", deny_unknown_fields)]
+pub enum OptimizerConfig {
+    Sgd {
+        lr: f64,
+    },
+    Momentum {
+        lr: f64,
+        #[serde(default = "default_beta")]
+        beta: f64,
+    },
+    Adam {
+        lr: f64,
+        #[serde(default = "default_beta")]
+        beta1: f64,
+        #[serde(default = "default_beta2")]
+        beta2: f64,
+        #[serde(default = "default_eps")]
+        eps: f64,
+    },
+}
+
+impl Default for OptimizerConfig {
+    fn default() -> Self {
+        OptimizerConfig::Sgd { lr: 0.03 }
+    }
+}
+
+impl OptimizerConfig {
+    pub fn lr(&self) -> f64 {
+        match self {
+            OptimizerConfig::Sgd { lr } | OptimizerConfig::Momentum { lr, .. } | OptimizerConfig::Adam { lr, .. } => *lr,
+        }
+    }
+
+    pub fn build(&self) -> Box<dyn Optimizer> {
+        match self {
+            OptimizerConfig::Sgd { lr } => Box::new(Sgd::new(*lr)),
+            OptimizerConfig::Momentum { lr, beta } => Box::new(Momentum::new(*lr).beta(*beta)),
+            OptimizerConfig::Adam { lr, beta1, beta2, eps } => Box::new(Adam::new(*lr).betas(*beta1, *beta2).eps(*eps)),
+        }
+    }
+}
+
+// SCHEDULER SECTION
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase", deny_unknown_fields)]
+pub enum SchedulerConfig {
+    // the optimizer's learning rate for the whole training
+    #[default]
+    Constant,
+    // linear decay from start to end (utils::alpha being start=0.03, end=0.01)
+    Linear {
+        start: f64,
+        end: f64,
+    },
+    // the optimizer's learning rate multiplied by gamma every `every` epochs
+    Step {
+        every: usize,
+        gamma: f64,
+    },
+}
+
+impl SchedulerConfig {
+    pub fn schedule(&self, lr: f64) -> Option<Schedule> {
+        match *self {
+            SchedulerConfig::Constant => None,
+            SchedulerConfig::Linear { start, end } =>
+                Some(Box::new(move |pass, epochs| start + (end - start) * pass as f64 / epochs as f64)),
+            SchedulerConfig::Step { every, gamma } =>
+                Some(Box::new(move |pass, _| lr * gamma.powi(pass / every as i32))),
+        }
+    }
+}
+
+// EARLY STOPPING SECTION
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct EarlyStoppingConfig {
+    // "loss", "val_loss" or "val_accuracy" (the only one expected to increase)
+    #[serde(default = "default_monitor")]
+    pub monitor: String,
+    #[serde(default = "default_patience")]
+    pub patience: usize,
+    #[serde(default)]
+    pub min_delta: f64,
+    #[serde(default = "default_true")]
+    pub restore_best_weights: bool,
+}
+
+impl EarlyStoppingConfig {
+    pub fn callback(&self) -> EarlyStopping {
+        EarlyStopping::new(&self.monitor)
+            .mode(if self.monitor == "val_accuracy" { Mode::Max } else { Mode::Min })
+            .patience(self.patience)
+            .min_delta(self.min_delta)
+            .restore_best_weights(self.restore_best_weights)
+    }
+}
+
+// CROSS VALIDATION SECTION
+// k-fold search of the best L2 lambda on the training set, replacing the regularizer's one
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CvConfig {
+    #[serde(default = "default_k")]
+    pub k: usize,
+    // start, end and step of the searched values
+    pub lambda: (f64, f64, f64),
+}
+
+// EXPERIMENT CONFIGURATION
+// everything a training run depends on, loaded from a JSON file
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Experiment {
+    pub name: String,
+    // where the run's files are written, runs/<name> if missing
+    pub output_dir: Option<String>,
+    #[serde(default)]
+    pub dataset: DatasetConfig,
+    #[serde(default)]
+    pub preprocessing: Vec<PreprocessingConfig>,
+    pub model: ModelConfig,
+    #[serde(default = "default_loss")]
+    pub loss: String,
+    #[serde(default)]
+    pub regularizer: RegularizerConfig,
+    #[serde(default)]
+    pub optimizer: OptimizerConfig,
+    #[serde(default)]
+    pub scheduler: SchedulerConfig,
+    #[serde(default = "default_epochs")]
+    pub epochs: usize,
+    pub early_stopping: Option<EarlyStoppingConfig>,
+    pub cv: Option<CvConfig>,
+}
+
+impl Experiment {
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, ConfigError> {
+        Experiment::parse(&fs::read_to_string(path).map_err(ConfigError::Io)?)
+    }
+
+    pub fn parse(content: &str) -> Result<Self, ConfigError> {
+        let experiment: Experiment = serde_json::from_str(content).map_err(ConfigError::Parse)?;
+        experiment.validate()?;
+
+        Ok(experiment)
+    }
+
+    // the configuration with every default filled in, so that a run can be reproduced from it
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> std::io::Result<()> {
+        fs::write(path, serde_json::to_string_pretty(self)?)
+    }
+
+    pub fn output_dir(&self) -> PathBuf {
+        match &self.output_dir {
+            Some(dir) => PathBuf::from(dir),
+            None => Path::new("runs").join(&self.name),
+        }
+    }
+
+    // every problem found, not just the first one
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        let mut problems = Vec::new();
+        let mut check = |ok: bool, field: &str, message: String| {
+            if !ok {
+                problems.push(Problem { field: field.to_string(), message });
+            }
+        };
+        let positive = |v: f64| v.is_finite() && v > 0.0;
+
+        check(
+            !self.name.trim().is_empty() && !self.name.contains(['/', '\\']),
+            "name",
+            format!("expected a non-empty name without path separators, found '{}'", self.name),
+        );
+
+        // dataset
+        let ds = &self.dataset;
+        for (field, ratio) in [("dataset.validation", ds.validation), ("dataset.test", ds.test)] {
+            check((0.0..1.0).contains(&ratio), field, format!("expected a ratio from 0 (included) to 1 (excluded), found {}", ratio));
+        }
+        check(
+            ds.validation + ds.test < 1.0,
+            "dataset",
+            format!("validation and test ratios sum up to {}, leaving no training data", ds.validation + ds.test),
+        );
+        if let Some(path) = &ds.path {
+            check(Path::new(path).is_file(), "dataset.path", format!("file '{}' not found", path));
+        }
+
+        // preprocessing
+        for (i, p) in self.preprocessing.iter().enumerate() {
+            match p {
+                PreprocessingConfig::MinMax { range } => check(
+                    range.0 < range.1,
+                    &format!("preprocessing[{}].range", i),
+                    format!("expected a lower bound smaller than the upper one, found [{}, {}]", range.0, range.1),
+                ),
+                PreprocessingConfig::OneHot { columns } => check(
+                    !columns.is_empty(),
+                    &format!("preprocessing[{}].columns", i),
+                    String::from("expected at least one categorical column"),
+                ),
+                _ => {}
+            }
+        }
+
+        // model
+        let arch = &self.model.arch;
+        check(!arch.is_empty(), "model.arch", String::from("expected at least one layer"));
+        for (i, n) in arch.iter().enumerate() {
+            check(*n > 0, &format!("model.arch[{}]", i), String::from("layers need at least 1 neuron"));
+        }
+        if let Some(last) = arch.last() {
+            check(*last == 1, "model.arch", format!("the output layer must have 1 neuron, found {}", last));
+        }
+        if let Some(activations) = &self.model.activations {
+            check(
+                activations.len() == arch.len(),
+                "model.activations",
+                format!("expected {} activations (one per layer), found {}", arch.len(), activations.len()),
+            );
+        }
+
+        // training
+        check(
+            LOSSES.contains(&self.loss.as_str()),
+            "loss",
+            format!("unknown loss '{}', expected one of {}", self.loss, LOSSES.join(", ")),
+        );
+        let lambda = self.regularizer.lambda();
+        check(lambda.is_finite() && lambda >= 0.0, "regularizer.lambda", format!("expected a non-negative number, found {}", lambda));
+        check(positive(self.optimizer.lr()), "optimizer.lr", format!("expected a positive number, found {}", self.optimizer.lr()));
+        match self.optimizer {
+            OptimizerConfig::Momentum { beta, .. } =>
+                check((0.0..1.0).contains(&beta), "optimizer.beta", format!("expected a number from 0 to 1 (excluded), found {}", beta)),
+            OptimizerConfig::Adam { beta1, beta2, eps, .. } => {
+                check((0.0..1.0).contains(&beta1), "optimizer.beta1", format!("expected a number from 0 to 1 (excluded), found {}", beta1));
+                check((0.0..1.0).contains(&beta2), "optimizer.beta2", format!("expected a number from 0 to 1 (excluded), found {}", beta2));
+                check(positive(eps), "optimizer.eps", format!("expected a positive number, found {}", eps));
+            }
+            OptimizerConfig::Sgd { .. } => {}
+        }
+        match self.scheduler {
+            SchedulerConfig::Linear { start, end } => {
+                check(positive(start), "scheduler.start", format!("expected a positive number, found {}", start));
+                check(positive(end), "scheduler.end", format!("expected a positive number, found {}", end));
+            }
+            SchedulerConfig::Step { every, gamma } => {
+                check(
+                    every > 0 && every <= i32::MAX as usize,
+                    "scheduler.every",
+                    format!("expected from 1 to {} epochs, found {}", i32::MAX, every),
+                );
+                check(positive(gamma), "scheduler.gamma", format!("expected a positive number, found {}", gamma));
+            }
+            SchedulerConfig::Constant => {}
+        }
+        check(self.epochs > 0, "epochs", String::from("expected at least 1 epoch"));
+
+        if let Some(es) = &self.early_stopping {
+            let monitors = ["loss", "val_loss", "val_accuracy"];
+            check(
+                monitors.contains(&es.monitor.as_str()),
+                "early_stopping.monitor",
+                format!("unknown metric '{}', expected one of {}", es.monitor, monitors.join(", ")),
+            );
+            check(
+                !es.monitor.starts_with("val_") || ds.validation > 0.0,
+                "early_stopping.monitor",
+                format!("'{}' needs a validation set, but dataset.validation is 0", es.monitor),
+            );
+        }
+
+        if let Some(cv) = &self.cv {
+            let (start, end, step) = cv.lambda;
+            check(cv.k >= 2, "cv.k", format!("expected at least 2 folds, found {}", cv.k));
+            let range = start >= 0.0 && start <= end && end.is_finite() && positive(step);
+            check(
+                range,
+                "cv.lambda",
+                format!("expected [start, end, step] with 0 <= start <= end < inf and step > 0, found [{}, {}, {}]", start, end, step),
+            );
+            // an end too far to be reached in a reasonable number of steps
+            let lambdas = ((end - start) / step).floor() + 1.0;
+            check(
+                !range || lambdas <= MAX_LAMBDAS,
+                "cv.lambda",
+                format!("expected at most {} lambdas, found {}", MAX_LAMBDAS, lambdas),
+            );
+        }
+
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(ConfigError::Invalid(problems))
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn problems(content: &str) -> Vec<Problem> {
+        match Experiment::parse(content) {
+            Err(ConfigError::Invalid(p)) => p,
+            other => panic!("expected validation problems, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_defaults() {
+        let e = Experiment::parse(r#"{"name": "tiny", "model": {"arch": [4, 1]}}"#).unwrap();
+
+        assert_eq!(e.dataset, DatasetConfig::default());
+        assert_eq!(e.loss, "mse");
+        assert_eq!(e.epochs, 50);
+        assert_eq!(e.optimizer, OptimizerConfig::Sgd { lr: 0.03 });
+        assert_eq!(e.model.activations(), vec![Activation::ReLU, Activation::Linear]);
+        assert_eq!(e.output_dir(), Path::new("runs").join("tiny"));
+    }
+
+    #[test]
+    fn test_sample_experiment() {
+        let e = Experiment::parse(include_str!("../../experiments/moons.json")).unwrap();
+
+        assert_eq!(e.model.arch, vec![16, 16, 1]);
+        assert_eq!(e.preprocessing, vec![PreprocessingConfig::Standard]);
+        assert_eq!(e.cv.as_ref().map(|cv| cv.k), Some(10));
+
+        // the echoed configuration gives back the same experiment
+        let echoed = serde_json::to_string(&e).unwrap();
+        assert_eq!(Experiment::parse(&echoed).unwrap(), e);
+    }
+
+    #[test]
+    fn test_schedules() {
+        let linear = SchedulerConfig::Linear { start: 0.03, end: 0.01 }.schedule(0.5).unwrap();
+        assert_eq!(linear(0, 50), 0.03);
+        assert_eq!((linear(25, 50) * 1_000_f64).round() / 1_000_f64, 0.02);
+
+        let step = SchedulerConfig::Step { every: 10, gamma: 0.5 }.schedule(0.4).unwrap();
+        assert_eq!(step(9, 50), 0.4);
+        assert_eq!(step(10, 50), 0.2);
+        assert!(SchedulerConfig::Constant.schedule(0.4).is_none());
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let unknown = Experiment::parse(r#"{"name": "x", "model": {"arch": [1]}, "epoch": 3}"#).unwrap_err();
+        assert!(unknown.to_string().contains("unknown field `epoch`"));
+
+        let variant = Experiment::parse(r#"{"name": "x", "model": {"arch": [1]}, "optimizer": {"type": "rmsprop", "lr": 0.1}}"#).unwrap_err();
+        assert!(variant.to_string().contains("unknown variant `rmsprop`"));
+    }
+
+    #[test]
+    fn test_validation() {
+        let p = problems(r#"{
+            "name": "bad",
+            "dataset": {"validation": 0.5, "test": 0.6},
+            "model": {"arch": [4, 0, 2], "activations": ["relu"]},
+            "loss": "mae",
+            "optimizer": {"type": "adam", "lr": -1},
+            "early_stopping": {"monitor": "accuracy"},
+            "cv": {"k": 1, "lambda": [0.1, 0.0, 0.01]}
+        }"#);
+        let fields: Vec<&str> = p.iter().map(|p| p.field.as_str()).collect();
+
+        assert_eq!(fields, vec![
+            "dataset", "model.arch[1]", "model.arch", "model.activations", "loss",
+            "optimizer.lr", "early_stopping.monitor", "cv.k", "cv.lambda",
+        ]);
+        assert_eq!(p[2].message, "the output layer must have 1 neuron, found 2");
+        assert_eq!(p[4].message, "unknown loss 'mae', expected one of mse, hinge");
+
+        let p = problems(r#"{"name": "x", "dataset": {"validation": 0}, "model": {"arch": [1]}, "early_stopping": {}}"#);
+        assert_eq!(p[0].message, "'val_loss' needs a validation set, but dataset.validation is 0");
+
+        // ranges that would take xval forever, and steps longer than the epochs a schedule is given (i32)
+        let p = problems(r#"{"name": "x", "model": {"arch": [1]}, "cv": {"lambda": [0.0, 1.0, 1e-4]}}"#);
+        assert_eq!(p[0].message, "expected at most 1000 lambdas, found 10001");
+        let p = problems(&format!(r#"{{"name": "x", "model": {{"arch": [1]}}, "scheduler": {{"type": "step", "every": {}, "gamma": 0.5}}}}"#, i32::MAX as usize + 1));
+        assert_eq!(p[0].field, "scheduler.every");
+        assert!(Experiment::parse(r#"{"name": "x", "model": {"arch": [1]}, "cv": {"lambda": [0.0, 1.0, 0.002]}}"#).is_ok());
+    }
+}
@@ -1,3 +1,7 @@
+use std::f64::consts::PI;
+use rand::{Rng, SeedableRng, StdRng};
+use rand::distributions::{IndependentSample, Normal};
+
 // following are the inputs and labels datasets
 // generated with scikit-learn's make_moon method (n_samples=100, noise=0.1)
 
@@ -111,4 +115,251 @@ pub const LBLS_DATASET: [f64; 100] = [
     1.0, 1.0, 1.0, -1.0, 1.0, -1.0, 1.0, -1.0, -1.0, 1.0, -1.0, 1.0, -1.0, 1.0, 1.0, -1.0, -1.0, 1.0, -1.0, -1.0, 1.0, -1.0,
     -1.0, 1.0, -1.0, 1.0, -1.0, -1.0, -1.0, 1.0, 1.0, 1.0, -1.0, -1.0, -1.0, -1.0, -1.0, 1.0, -1.0, 1.0, 1.0, 1.0, 1.0, 1.0,
     -1.0, 1.0, -1.0, 1.0, 1.0, 1.0, -1.0, 1.0, 1.0, 1.0, -1.0, 1.0
-];
\ No newline at end of file
+];
+
+// SYNTHETIC DATASET GENERATORS
+// all of them return the features (one row per sample) and the labels in the same shape as
+// INP_DATASET and LBLS_DATASET, with binary problems labelled -1.0/1.0 (as in LBLS_DATASET)
+// and multiclass ones labelled with the class index (0.0, 1.0, 2.0, ...)
+
+// deterministic random number generator, so that the same seed always gives the same data
+// (the whole seed is used: one word on 64-bit targets, two on 32-bit ones)
+pub fn seeded_rng(seed: u64) -> StdRng {
+    let words: Vec<usize> = seed.to_le_bytes()
+        .chunks(std::mem::size_of::<usize>())
+        .map(|c| c.iter().rev().fold(0, |w, b| (w << 8) | *b as usize))
+        .collect();
+
+    StdRng::from_seed(&words[..])
+}
+
+// gaussian noise with the given standard deviation (no noise at all when it's not positive)
+fn gaussian(rng: &mut StdRng, std_dev: f64) -> f64 {
+    if std_dev > 0.0 {
+        Normal::new(0.0, std_dev).ind_sample(rng)
+    } else {
+        0.0
+    }
+}
+
+// evenly spaced values from start to end (included or not)
+fn linspace(start: f64, end: f64, n: usize, endpoint: bool) -> Vec<f64> {
+    let steps = if endpoint { n.saturating_sub(1).max(1) } else { n.max(1) };
+    (0..n).map(|i| start + (end - start) * i as f64 / steps as f64).collect()
+}
+
+// add noise to every feature and shuffle samples and labels the same way
+fn finalize(mut features: Vec<Vec<f64>>, labels: Vec<f64>, noise: f64, rng: &mut StdRng) -> (Vec<Vec<f64>>, Vec<f64>) {
+    for row in features.iter_mut() {
+        for x in row.iter_mut() {
+            *x += gaussian(rng, noise);
+        }
+    }
+
+    let mut indexes: Vec<usize> = (0..labels.len()).collect();
+    rng.shuffle(&mut indexes);
+    let shuffled_features = indexes.iter().map(|i| std::mem::take(&mut features[*i])).collect();
+    let shuffled_labels = indexes.iter().map(|i| labels[*i]).collect();
+
+    (shuffled_features, shuffled_labels)
+}
+
+// two interleaving half circles (same as scikit-learn's make_moons)
+pub fn make_moons(n_samples: usize, noise: f64, seed: u64) -> (Vec<Vec<f64>>, Vec<f64>) {
+    let mut rng = seeded_rng(seed);
+    let n_outer = n_samples / 2;
+    let n_inner = n_samples - n_outer;
+
+    let mut features = Vec::with_capacity(n_samples);
+    let mut labels = Vec::with_capacity(n_samples);
+    for t in linspace(0.0, PI, n_outer, true) {
+        features.push(vec![t.cos(), t.sin()]);
+        labels.push(-1.0);
+    }
+    for t in linspace(0.0, PI, n_inner, true) {
+        features.push(vec![1.0 - t.cos(), 1.0 - t.sin() - 0.5]);
+        labels.push(1.0);
+    }
+
+    finalize(features, labels, noise, &mut rng)
+}
+
+// a large circle containing a smaller one, factor being the ratio between their radiuses
+pub fn make_circles(n_samples: usize, noise: f64, factor: f64, seed: u64) -> (Vec<Vec<f64>>, Vec<f64>) {
+    let mut rng = seeded_rng(seed);
+    let n_outer = n_samples / 2;
+    let n_inner = n_samples - n_outer;
+
+    let mut features = Vec::with_capacity(n_samples);
+    let mut labels = Vec::with_capacity(n_samples);
+    for t in linspace(0.0, 2.0 * PI, n_outer, false) {
+        features.push(vec![t.cos(), t.sin()]);
+        labels.push(-1.0);
+    }
+    for t in linspace(0.0, 2.0 * PI, n_inner, false) {
+        features.push(vec![factor * t.cos(), factor * t.sin()]);
+        labels.push(1.0);
+    }
+
+    finalize(features, labels, noise, &mut rng)
+}
+
+// isotropic gaussian blobs (one per class) with centers drawn uniformly in the (-10, 10) box
+pub fn make_blobs(n_samples: usize, n_features: usize, centers: usize, cluster_std: f64, seed: u64) -> (Vec<Vec<f64>>, Vec<f64>) {
+    let mut rng = seeded_rng(seed);
+    let centers: Vec<Vec<f64>> = (0..centers.max(1))
+        .map(|_| (0..n_features).map(|_| rng.gen_range(-10.0, 10.0)).collect())
+        .collect();
+
+    let mut features = Vec::with_capacity(n_samples);
+    let mut labels = Vec::with_capacity(n_samples);
+    for i in 0..n_samples {
+        let class = i % centers.len();
+        features.push(centers[class].clone());
+        labels.push(class as f64);
+    }
+
+    finalize(features, labels, cluster_std, &mut rng)
+}
+
+// intertwined spirals (two arms are labelled -1.0/1.0, more arms with their index)
+pub fn make_spirals(n_samples: usize, arms: usize, noise: f64, seed: u64) -> (Vec<Vec<f64>>, Vec<f64>) {
+    let mut rng = seeded_rng(seed);
+    let arms = arms.max(2);
+
+    let mut features = Vec::with_capacity(n_samples);
+    let mut labels = Vec::with_capacity(n_samples);
+    for arm in 0..arms {
+        // spread the remainder over the first arms
+        let n = n_samples / arms + usize::from(arm < n_samples % arms);
+        let offset = 2.0 * PI * arm as f64 / arms as f64;
+        for r in linspace(0.0, 1.0, n, true) {
+            let theta = offset + 2.0 * PI * 1.5 * r;
+            features.push(vec![r * theta.cos(), r * theta.sin()]);
+            labels.push(if arms == 2 { if arm == 0 { -1.0 } else { 1.0 } } else { arm as f64 });
+        }
+    }
+
+    finalize(features, labels, noise, &mut rng)
+}
+
+// points uniformly drawn in the [-1, 1] square labelled with the sign of the product of their coordinates
+pub fn make_xor(n_samples: usize, noise: f64, seed: u64) -> (Vec<Vec<f64>>, Vec<f64>) {
+    let mut rng = seeded_rng(seed);
+
+    let mut features = Vec::with_capacity(n_samples);
+    let mut labels = Vec::with_capacity(n_samples);
+    for _ in 0..n_samples {
+        let (x, y): (f64, f64) = (rng.gen_range(-1.0, 1.0), rng.gen_range(-1.0, 1.0));
+        features.push(vec![x, y]);
+        labels.push(if x * y >= 0.0 { 1.0 } else { -1.0 });
+    }
+
+    finalize(features, labels, noise, &mut rng)
+}
+
+// standard normal features and targets given by a random linear combination of them plus a bias,
+// with gaussian noise added to the targets (not to the features)
+pub fn make_regression(n_samples: usize, n_features: usize, noise: f64, seed: u64) -> (Vec<Vec<f64>>, Vec<f64>) {
+    let mut rng = seeded_rng(seed);
+    let coefs: Vec<f64> = (0..n_features).map(|_| rng.gen_range(-1.0, 1.0)).collect();
+    let bias: f64 = rng.gen_range(-1.0, 1.0);
+
+    let features: Vec<Vec<f64>> = (0..n_samples)
+        .map(|_| (0..n_features).map(|_| gaussian(&mut rng, 1.0)).collect())
+        .collect();
+    let targets = features.iter()
+        .map(|x| x.iter().zip(coefs.iter()).map(|(x, c)| x * c).sum::<f64>() + bias + gaussian(&mut rng, noise))
+        .collect();
+
+    (features, targets)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn count(labels: &[f64], label: f64) -> usize {
+        labels.iter().filter(|l| **l == label).count()
+    }
+
+    #[test]
+    fn test_make_moons() {
+        let (features, labels) = make_moons(100, 0.0, 42);
+
+        assert_eq!(features.len(), 100);
+        assert!(features.iter().all(|r| r.len() == 2));
+        assert_eq!(count(&labels, -1.0), 50);
+        assert_eq!(count(&labels, 1.0), 50);
+
+        // without noise the outer moon lies on the unit circle
+        assert!(features.iter().zip(labels.iter())
+            .filter(|(_, l)| **l == -1.0)
+            .all(|(r, _)| ((r[0].powi(2) + r[1].powi(2)).sqrt() - 1.0).abs() < 1e-9));
+
+        // same seed same data, different seed different order
+        assert_eq!(make_moons(100, 0.1, 42), make_moons(100, 0.1, 42));
+        assert_ne!(make_moons(100, 0.1, 42), make_moons(100, 0.1, 7));
+        assert_ne!(make_moons(100, 0.1, 42), make_moons(100, 0.1, 42 + (1 << 32)));
+    }
+
+    #[test]
+    fn test_make_circles() {
+        let (features, labels) = make_circles(51, 0.0, 0.5, 0);
+
+        assert_eq!(count(&labels, -1.0), 25);
+        assert_eq!(count(&labels, 1.0), 26);
+        assert!(features.iter().zip(labels.iter())
+            .all(|(r, l)| {
+                let radius = (r[0].powi(2) + r[1].powi(2)).sqrt();
+                (radius - if *l == 1.0 { 0.5 } else { 1.0 }).abs() < 1e-9
+            }));
+    }
+
+    #[test]
+    fn test_make_blobs() {
+        let (features, labels) = make_blobs(90, 4, 3, 0.5, 1);
+
+        assert_eq!(features.len(), 90);
+        assert!(features.iter().all(|r| r.len() == 4));
+        for class in 0..3 {
+            assert_eq!(count(&labels, class as f64), 30);
+        }
+    }
+
+    #[test]
+    fn test_make_spirals() {
+        let (features, labels) = make_spirals(100, 2, 0.0, 3);
+        assert_eq!(features.len(), 100);
+        assert_eq!(count(&labels, -1.0), 50);
+        assert_eq!(count(&labels, 1.0), 50);
+
+        let (features, labels) = make_spirals(100, 3, 0.05, 3);
+        assert_eq!(features.len(), 100);
+        assert_eq!(count(&labels, 0.0), 34);
+        assert_eq!(count(&labels, 1.0), 33);
+        assert_eq!(count(&labels, 2.0), 33);
+    }
+
+    #[test]
+    fn test_make_xor() {
+        let (features, labels) = make_xor(200, 0.0, 5);
+
+        assert_eq!(features.len(), 200);
+        assert!(features.iter().zip(labels.iter())
+            .all(|(r, l)| (r[0] * r[1] >= 0.0) == (*l > 0.0)));
+    }
+
+    #[test]
+    fn test_make_regression() {
+        let (features, targets) = make_regression(50, 3, 0.0, 9);
+        let (noisy_features, noisy_targets) = make_regression(50, 3, 0.5, 9);
+
+        assert_eq!(features.len(), 50);
+        assert!(features.iter().all(|r| r.len() == 3));
+
+        // noise only affects the targets
+        assert_eq!(features, noisy_features);
+        assert!(targets.iter().zip(noisy_targets.iter()).any(|(a, b)| a != b));
+    }
+}
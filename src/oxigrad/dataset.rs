@@ -0,0 +1,321 @@
+use std::fmt::Display;
+use std::fs;
+use std::path::Path;
+use serde::{Serialize, Deserialize};
+use super::error::OxiError;
+
+// DATASET IMPLEMENTATION
+// feature matrix (one row per sample, any width) and the respective targets
+#[derive(Clone, Debug, PartialEq)]
+pub struct Dataset {
+    pub features: Vec<Vec<f64>>,
+    pub targets: Vec<f64>,
+    pub feature_names: Vec<String>,
+}
+
+impl Dataset {
+    // every row needs as many features as the first one, and a target
+    pub fn new(features: Vec<Vec<f64>>, targets: Vec<f64>) -> Result<Self, OxiError> {
+        if features.len() != targets.len() {
+            return Err(OxiError::LengthMismatch { data: features.len(), labels: targets.len() });
+        }
+        let width = features.first().map_or(0, |r| r.len());
+        if let Some((row, r)) = features.iter().enumerate().find(|(_, r)| r.len() != width) {
+            return Err(OxiError::RowWidth { row, expected: width, found: r.len() });
+        }
+
+        Ok(Dataset {
+            features,
+            targets,
+            feature_names: (0..width).map(|i| format!("x{}", i)).collect(),
+        })
+    }
+
+    // build a dataset from rows, e.g. the INP_DATASET and LBLS_DATASET constants
+    pub fn from_rows<R: AsRef<[f64]>>(rows: &[R], targets: &[f64]) -> Result<Self, OxiError> {
+        Dataset::new(
+            rows.iter().map(|r| r.as_ref().to_vec()).collect(),
+            targets.to_vec(),
+        )
+    }
+
+    pub fn from_csv<P: AsRef<Path>>(path: P, options: &CsvOptions) -> Result<Self, CsvError> {
+        let content = fs::read_to_string(path).map_err(CsvError::Io)?;
+        Dataset::parse_csv(&content, options)
+    }
+
+    pub fn parse_csv(content: &str, options: &CsvOptions) -> Result<Self, CsvError> {
+        // (line number, fields) for each non blank line
+        let mut rows = content.lines()
+            .enumerate()
+            .filter(|(_, l)| !l.trim().is_empty())
+            .map(|(i, l)| (i + 1, split_fields(l, options.delimiter)));
+
+        let (first_line, first) = rows.next().ok_or(CsvError::Empty)?;
+        let width = first.len();
+
+        // without an explicit choice the first row is a header if any of its fields is not a number
+        // (missing values excluded, so that a first data row with one is reported rather than dropped)
+        let has_header = options.header
+            .unwrap_or_else(|| first.iter().any(|f| !is_missing(f) && f.parse::<f64>().is_err()));
+        let names: Vec<String> = if has_header {
+            first.clone()
+        } else {
+            (0..width).map(|i| format!("x{}", i)).collect()
+        };
+
+        // resolve label and features columns
+        let label = match options.label.as_ref() {
+            Some(c) => c.resolve(&names)?,
+            None => width - 1,
+        };
+        let features = match options.features.as_ref() {
+            Some(cs) => cs.iter().map(|c| c.resolve(&names)).collect::<Result<Vec<usize>, CsvError>>()?,
+            None => (0..width).filter(|i| *i != label).collect(),
+        };
+
+        let mut ds = Dataset {
+            features: Vec::new(),
+            targets: Vec::new(),
+            feature_names: features.iter().map(|i| names[*i].clone()).collect(),
+        };
+
+        let body = if has_header { None } else { Some((first_line, first)) };
+        for (line, fields) in body.into_iter().chain(rows) {
+            if fields.len() != width {
+                return Err(CsvError::RowLength { line, expected: width, found: fields.len() });
+            }
+
+            let parse = |i: usize| parse_field(&fields[i], line, &names[i]);
+            ds.features.push(features.iter().map(|i| parse(*i)).collect::<Result<Vec<f64>, CsvError>>()?);
+            ds.targets.push(parse(label)?);
+        }
+
+        if ds.is_empty() {
+            return Err(CsvError::Empty);
+        }
+
+        Ok(ds)
+    }
+
+    pub fn len(&self) -> usize {
+        self.targets.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.targets.is_empty()
+    }
+
+    // number of features for each sample
+    pub fn width(&self) -> usize {
+        self.features.first().map_or(0, |r| r.len())
+    }
+}
+
+// CSV LOADER OPTIONS
+// columns are given by position or by header name (e.g. 2 or "label" in a JSON file)
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum Column {
+    Index(usize),
+    Name(String),
+}
+
+impl Column {
+    fn resolve(&self, names: &[String]) -> Result<usize, CsvError> {
+        match self {
+            Column::Index(i) if *i < names.len() => Ok(*i),
+            Column::Name(n) => names.iter()
+                .position(|h| h == n)
+                .ok_or_else(|| CsvError::UnknownColumn(n.clone())),
+            Column::Index(i) => Err(CsvError::UnknownColumn(i.to_string())),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct CsvOptions {
+    pub delimiter: char,
+    // None means the header is detected from the first row
+    pub header: Option<bool>,
+    // None means every column but the label one
+    pub features: Option<Vec<Column>>,
+    // None means the last column
+    pub label: Option<Column>,
+}
+
+impl Default for CsvOptions {
+    fn default() -> Self {
+        CsvOptions {
+            delimiter: ',',
+            header: None,
+            features: None,
+            label: None,
+        }
+    }
+}
+
+impl CsvOptions {
+    pub fn delimiter(mut self, delimiter: char) -> Self {
+        self.delimiter = delimiter;
+        self
+    }
+
+    pub fn header(mut self, header: bool) -> Self {
+        self.header = Some(header);
+        self
+    }
+
+    pub fn features(mut self, columns: Vec<Column>) -> Self {
+        self.features = Some(columns);
+        self
+    }
+
+    pub fn label(mut self, column: Column) -> Self {
+        self.label = Some(column);
+        self
+    }
+}
+
+// CSV LOADER ERRORS
+#[derive(Debug)]
+pub enum CsvError {
+    Io(std::io::Error),
+    Empty,
+    UnknownColumn(String),
+    RowLength { line: usize, expected: usize, found: usize },
+    MissingValue { line: usize, column: String },
+    InvalidNumber { line: usize, column: String, value: String },
+}
+
+impl Display for CsvError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CsvError::Io(e) => write!(f, "cannot read CSV file: {}", e),
+            CsvError::Empty => write!(f, "CSV file contains no data"),
+            CsvError::UnknownColumn(c) => write!(f, "unknown column '{}'", c),
+            CsvError::RowLength { line, expected, found } =>
+                write!(f, "line {}: expected {} fields, found {}", line, expected, found),
+            CsvError::MissingValue { line, column } =>
+                write!(f, "line {}: missing value in column '{}'", line, column),
+            CsvError::InvalidNumber { line, column, value } =>
+                write!(f, "line {}: invalid number '{}' in column '{}'", line, value, column),
+        }
+    }
+}
+
+impl std::error::Error for CsvError {}
+
+fn split_fields(line: &str, delimiter: char) -> Vec<String> {
+    line.split(delimiter)
+        .map(|f| f.trim().trim_matches('"').to_string())
+        .collect()
+}
+
+fn is_missing(field: &str) -> bool {
+    matches!(field, "" | "NA" | "N/A" | "NaN" | "nan" | "?")
+}
+
+fn parse_field(field: &str, line: usize, column: &str) -> Result<f64, CsvError> {
+    if is_missing(field) {
+        return Err(CsvError::MissingValue {
+            line,
+            column: column.to_string(),
+        });
+    }
+
+    field.parse::<f64>().map_err(|_| CsvError::InvalidNumber {
+        line,
+        column: column.to_string(),
+        value: field.to_string(),
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_from_rows() {
+        let ds = Dataset::from_rows(&[[1.0, 2.0], [3.0, 4.0]], &[-1.0, 1.0]).unwrap();
+
+        assert_eq!(ds.len(), 2);
+        assert_eq!(ds.width(), 2);
+        assert_eq!(ds.features[1], vec![3.0, 4.0]);
+        assert_eq!(ds.feature_names, vec!["x0", "x1"]);
+
+        assert!(matches!(
+            Dataset::from_rows(&[[1.0, 2.0]], &[-1.0, 1.0]),
+            Err(OxiError::LengthMismatch { data: 1, labels: 2 }),
+        ));
+        assert!(matches!(
+            Dataset::new(vec![vec![1.0, 2.0], vec![3.0]], vec![-1.0, 1.0]),
+            Err(OxiError::RowWidth { row: 1, expected: 2, found: 1 }),
+        ));
+    }
+
+    #[test]
+    fn test_csv_header_detection() {
+        let with_header = "a,b,label\n1.0,2.0,-1\n3.0,4.0,1\n";
+        let ds = Dataset::parse_csv(with_header, &CsvOptions::default()).unwrap();
+        assert_eq!(ds.features, vec![vec![1.0, 2.0], vec![3.0, 4.0]]);
+        assert_eq!(ds.targets, vec![-1.0, 1.0]);
+        assert_eq!(ds.feature_names, vec!["a", "b"]);
+
+        let without_header = "1.0,2.0,-1\n\n3.0,4.0,1\n";
+        let ds = Dataset::parse_csv(without_header, &CsvOptions::default()).unwrap();
+        assert_eq!(ds.len(), 2);
+        assert_eq!(ds.feature_names, vec!["x0", "x1"]);
+
+        // a missing value doesn't make the first data row a header
+        for first in ["1.0,,-1", "NA,2.0,-1", "1.0,?,-1"] {
+            let content = format!("{}\n3.0,4.0,1\n", first);
+            assert!(matches!(
+                Dataset::parse_csv(&content, &CsvOptions::default()),
+                Err(CsvError::MissingValue { line: 1, .. }),
+            ));
+        }
+    }
+
+    #[test]
+    fn test_csv_columns_and_delimiter() {
+        let content = "label;a;b;c\n1;0.1;0.2;0.3\n-1;0.4;0.5;0.6\n";
+        let options = CsvOptions::default()
+            .delimiter(';')
+            .label(Column::Name("label".to_string()))
+            .features(vec![Column::Name("c".to_string()), Column::Index(1)]);
+        let ds = Dataset::parse_csv(content, &options).unwrap();
+
+        assert_eq!(ds.features, vec![vec![0.3, 0.1], vec![0.6, 0.4]]);
+        assert_eq!(ds.targets, vec![1.0, -1.0]);
+
+        let options = CsvOptions::default().label(Column::Name("missing".to_string()));
+        assert!(matches!(Dataset::parse_csv(content, &options), Err(CsvError::UnknownColumn(_))));
+    }
+
+    #[test]
+    fn test_csv_errors() {
+        let missing = "a,b,label\n1.0,2.0,-1\n3.0,,1\n";
+        match Dataset::parse_csv(missing, &CsvOptions::default()) {
+            Err(CsvError::MissingValue { line, column }) => {
+                assert_eq!(line, 3);
+                assert_eq!(column, "b");
+            },
+            other => panic!("unexpected result {:?}", other),
+        }
+
+        let short = "1.0,2.0,-1\n3.0,1\n";
+        assert!(matches!(
+            Dataset::parse_csv(short, &CsvOptions::default()),
+            Err(CsvError::RowLength { line: 2, expected: 3, found: 2 }),
+        ));
+
+        let invalid = "a,b,label\n1.0,x,-1\n";
+        assert!(matches!(
+            Dataset::parse_csv(invalid, &CsvOptions::default().header(true)),
+            Err(CsvError::InvalidNumber { line: 2, .. }),
+        ));
+
+        assert!(matches!(Dataset::parse_csv("a,b\n", &CsvOptions::default()), Err(CsvError::Empty)));
+    }
+}
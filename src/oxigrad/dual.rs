@@ -0,0 +1,217 @@
+use std::ops;
+use std::fmt::Display;
+use super::engine::Value;
+use super::functional::jacobian;
+
+// DUAL NUMBER IMPLEMENTATION
+// forward mode automatic differentiation: each number carries the derivative (tangent) of its value
+// w.r.t. a chosen direction, so one pass gives a Jacobian-vector product without building any graph
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Dual {
+    pub val: f64,
+    pub der: f64,
+}
+
+impl Dual {
+    pub fn new(val: f64, der: f64) -> Self {
+        Dual { val, der }
+    }
+
+    // a number the derivative isn't taken w.r.t.
+    pub fn constant(val: f64) -> Self {
+        Dual::new(val, 0.0)
+    }
+
+    // the number the derivative is taken w.r.t.
+    pub fn variable(val: f64) -> Self {
+        Dual::new(val, 1.0)
+    }
+
+    pub fn power(self, exp: f64) -> Self {
+        Dual::new(self.val.powf(exp), exp * self.val.powf(exp - 1.0) * self.der)
+    }
+
+    // same convention as Value::relu, the derivative in 0 is 1
+    pub fn relu(self) -> Self {
+        if self.val < 0.0 {
+            Dual::constant(0.0)
+        } else {
+            self
+        }
+    }
+
+    // counterparts of Value::detach and Value::stop_gradient, which coincide in forward mode
+    pub fn detach(self) -> Self {
+        Dual::constant(self.val)
+    }
+
+    pub fn stop_gradient(self) -> Self {
+        self.detach()
+    }
+}
+
+impl ops::Add<Dual> for Dual {
+    type Output = Dual;
+
+    fn add(self, other: Dual) -> Self::Output {
+        Dual::new(self.val + other.val, self.der + other.der)
+    }
+}
+
+impl ops::Add<f64> for Dual {
+    type Output = Dual;
+
+    fn add(self, other: f64) -> Self::Output {
+        self + Dual::constant(other)
+    }
+}
+
+impl ops::Sub<Dual> for Dual {
+    type Output = Dual;
+
+    fn sub(self, other: Dual) -> Self::Output {
+        self + -other
+    }
+}
+
+impl ops::Mul<Dual> for Dual {
+    type Output = Dual;
+
+    fn mul(self, other: Dual) -> Self::Output {
+        Dual::new(self.val * other.val, self.der * other.val + self.val * other.der)
+    }
+}
+
+impl ops::Div<Dual> for Dual {
+    type Output = Dual;
+
+    fn div(self, other: Dual) -> Self::Output {
+        self * other.power(-1.0)
+    }
+}
+
+impl ops::Div<f64> for Dual {
+    type Output = Dual;
+
+    fn div(self, other: f64) -> Self::Output {
+        self * Dual::constant(1.0/other)
+    }
+}
+
+impl ops::Neg for Dual {
+    type Output = Dual;
+
+    fn neg(self) -> Self::Output {
+        Dual::new(-self.val, -self.der)
+    }
+}
+
+impl std::iter::Sum<Dual> for Dual {
+    fn sum<I: Iterator<Item = Dual>>(iter: I) -> Self {
+        iter.fold(Dual::constant(0.0), |sum, el| sum + el)
+    }
+}
+
+impl Display for Dual {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("DUAL")
+            .field("VAL", &self.val)
+            .field("DER", &self.der)
+            .finish()
+    }
+}
+
+// JACOBIANS
+// values of f at x and its directional derivatives along v, i.e. the Jacobian-vector product J(x)v
+pub fn jvp<F>(f: F, x: &[f64], v: &[f64]) -> (Vec<f64>, Vec<f64>)
+    where F: Fn(&[Dual]) -> Vec<Dual>
+{
+    assert_eq!(x.len(), v.len(), "The direction needs as many components as the inputs");
+    let inputs: Vec<Dual> = x.iter().zip(v).map(|(x, v)| Dual::new(*x, *v)).collect();
+
+    f(&inputs).iter().map(|o| (o.val, o.der)).unzip()
+}
+
+// dense Jacobian of f at x (a row per output, a column per input) in forward mode, i.e. a pass per input
+// (see jacobian_reverse, better suited to functions with fewer outputs than inputs)
+pub fn jacobian_forward<F>(f: F, x: &[f64]) -> Vec<Vec<f64>>
+    where F: Fn(&[Dual]) -> Vec<Dual>
+{
+    let columns: Vec<Vec<f64>> = (0..x.len())
+        .map(|j| {
+            let direction: Vec<f64> = (0..x.len()).map(|i| if i == j { 1.0 } else { 0.0 }).collect();
+            jvp(&f, x, &direction).1
+        })
+        .collect();
+    let outputs = columns.first().map_or(0, |c| c.len());
+
+    (0..outputs).map(|i| columns.iter().map(|c| c[i]).collect()).collect()
+}
+
+// same as jacobian_forward in reverse mode, i.e. a graph built once and a pass per output (see functional::jacobian)
+pub fn jacobian_reverse<F>(f: F, x: &[f64]) -> Vec<Vec<f64>>
+    where F: Fn(&[Value]) -> Vec<Value>
+{
+    jacobian(f, x)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn round(m: Vec<Vec<f64>>) -> Vec<Vec<f64>> {
+        m.iter().map(|r| r.iter().map(|v| (v * 1_000_f64).round() / 1_000_f64).collect()).collect()
+    }
+
+    #[test]
+    fn test_ops() {
+        let x = Dual::variable(2.0);
+        let c = Dual::constant(3.0);
+
+        assert_eq!(x + c, Dual::new(5.0, 1.0));
+        assert_eq!(x - c, Dual::new(-1.0, 1.0));
+        assert_eq!(x * c, Dual::new(6.0, 3.0));
+        assert_eq!(c / x, Dual::new(1.5, -0.75));
+        assert_eq!(x / 4.0, Dual::new(0.5, 0.25));
+        assert_eq!(x.power(3.0), Dual::new(8.0, 12.0));
+        assert_eq!((-x).relu(), Dual::new(0.0, 0.0));
+        assert_eq!((x * x).stop_gradient(), Dual::new(4.0, 0.0));
+        assert_eq!([x, c, x].into_iter().sum::<Dual>(), Dual::new(7.0, 2.0));
+    }
+
+    #[test]
+    fn test_jvp() {
+        let (values, tangents) = jvp(|v| vec![v[0] * v[1], v[0] + 1.0], &[2.0, 3.0], &[1.0, -1.0]);
+
+        assert_eq!(values, vec![6.0, 3.0]);
+        assert_eq!(tangents, vec![1.0, 1.0]);
+    }
+
+    #[test]
+    fn test_jacobian_modes_agree() {
+        // the same function written for both modes, using every operation
+        let fwd = |v: &[Dual]| vec![
+            v[0] * v[1] + v[2] / v[0],
+            (v[0] - v[1]).power(2.0).relu() + (v[1] - v[2] * v[2]).relu(),
+            -(v[2].power(3.0) / 2.0) + v[0].stop_gradient() * v[1],
+            v.iter().copied().sum::<Dual>() + 1.0,
+        ];
+        let rev = |v: &[Value]| vec![
+            &(&v[0] * &v[1]) + &(&v[2] / &v[0]),
+            &(&v[0] - &v[1]).power(2.0).relu() + &(&v[1] - &(&v[2] * &v[2])).relu(),
+            &(-(v[2].power(3.0) / 2.0)) + &(&v[0].stop_gradient() * &v[1]),
+            &v.iter().sum::<Value>() + 1.0,
+        ];
+
+        for x in [[2.0, -1.0, 0.5], [0.3, 4.0, -2.0], [-1.5, 0.2, 1.0]] {
+            let j = round(jacobian_forward(fwd, &x));
+            assert_eq!(j.len(), 4);
+            assert!(j.iter().all(|r| r.len() == 3));
+            assert_eq!(j, round(jacobian_reverse(rev, &x)));
+        }
+
+        let j = jacobian_forward(fwd, &[2.0, -1.0, 0.5]);
+        assert_eq!(j[0], vec![-1.0 - 0.5 / 4.0, 2.0, 0.5]);
+        assert_eq!(j[2], vec![0.0, 2.0, -0.375]);
+    }
+}
@@ -1,9 +1,99 @@
 use std::ops;
 use std::rc::Rc;
 use std::cell::{Cell, RefCell};
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 use std::hash::{Hash, Hasher};
 use std::fmt::{Debug, Display};
+use super::error::{Anomaly, OxiError, Pass};
+
+// GRADIENT MODE
+// whether the operations on Values record the graph (children and backward closures) needed by backward
+thread_local! {
+    static GRAD_ENABLED: Cell<bool> = const { Cell::new(true) };
+}
+
+pub fn is_grad_enabled() -> bool {
+    GRAD_ENABLED.with(|g| g.get())
+}
+
+// while the returned guard is alive operations only compute data (e.g. for evaluation and inference),
+// the previous mode is restored when it's dropped so guards can be nested
+pub fn no_grad() -> NoGradGuard {
+    NoGradGuard { prev: GRAD_ENABLED.with(|g| g.replace(false)) }
+}
+
+#[must_use = "gradients are enabled again as soon as the guard is dropped"]
+pub struct NoGradGuard {
+    prev: bool,
+}
+
+impl Drop for NoGradGuard {
+    fn drop(&mut self) {
+        GRAD_ENABLED.with(|g| g.set(self.prev));
+    }
+}
+
+// ANOMALY DETECTION
+// whether the results of the operations and the gradients of the backward passes are checked for
+// NaNs and infinities, slowing both down (meant to find where a diverging training goes wrong)
+thread_local! {
+    static ANOMALY: Cell<bool> = const { Cell::new(false) };
+}
+
+pub fn is_anomaly_enabled() -> bool {
+    ANOMALY.with(|a| a.get())
+}
+
+// while the returned guard is alive the operations record a non finite result on the node they build
+// (operators can't return errors) and backward passes return OxiError::Anomaly on the first of those
+// nodes, or else on the first non finite gradient (from an operation or a hook), with the path from the
+// node backward was called on (the previous mode is restored on drop)
+pub fn detect_anomaly() -> AnomalyGuard {
+    AnomalyGuard { prev: ANOMALY.with(|a| a.replace(true)) }
+}
+
+#[must_use = "anomaly detection is disabled again as soon as the guard is dropped"]
+pub struct AnomalyGuard {
+    prev: bool,
+}
+
+impl Drop for AnomalyGuard {
+    fn drop(&mut self) {
+        ANOMALY.with(|a| a.set(self.prev));
+    }
+}
+
+// the anomaly of an operation's result, recorded on the node it builds (see Value::with_anomaly)
+fn check_forward(op: &str, inputs: &[&Value], data: f64) -> Option<Anomaly> {
+    (is_anomaly_enabled() && !data.is_finite()).then(|| Anomaly {
+        pass: Pass::Forward,
+        op: op.to_string(),
+        inputs: inputs.iter().map(|i| i.get_data()).collect(),
+        value: data,
+        path: vec![],
+    })
+}
+
+// BACKWARD OPTIONS
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BackwardOptions {
+    // reset the grads of the intermediate nodes before the pass, so running it again on the same
+    // graph doesn't accumulate into them
+    pub zero_grad: bool,
+    // keep the backward closures after the pass, otherwise they're freed along with what they hold
+    // and backpropagating through the graph again is an error
+    pub retain_graph: bool,
+}
+
+impl Default for BackwardOptions {
+    // the behaviour of Value::backward
+    fn default() -> Self {
+        BackwardOptions {
+            zero_grad: false,
+            retain_graph: true,
+        }
+    }
+}
 
 #[derive(Debug, Clone, Copy)]
 pub enum Operation {
@@ -13,15 +103,49 @@ pub enum Operation {
     Division,
     Power,
     ReLU,
+    StopGradient,
+    // built by Value::custom
+    Custom,
     None,
 }
 
+// CUSTOM OPERATIONS
+// differentiable operations defined outside of the engine, on any number of inputs (see Value::custom),
+// only available on Value: Dual, SyncValue and TapeValue have no way to run them
+pub trait CustomOp {
+    // shown in place of the operation in graph dumps
+    fn name(&self) -> &str;
+
+    fn forward(&self, inputs: &[f64]) -> f64;
+
+    // gradient w.r.t. each input given the inputs and the gradient of the output (i.e. the upstream one),
+    // exactly one per input
+    fn backward(&self, inputs: &[f64], grad: f64) -> Vec<f64>;
+}
+
+// given the gradient of the output as a node, the gradients of the children as nodes (one per child)
+type GradFn = Box<dyn Fn(&Value) -> Vec<Value>>;
+
 pub struct Core {
     pub data: Rc<Cell<f64>>,
     pub grad: Rc<Cell<f64>>,
     op: Option<Operation>,
     pub children: Option<Vec<Value>>,
-    backward: Option<Box<dyn Fn() -> ()>>,
+    backward: Option<Box<dyn Fn()>>,
+    // symbolic counterpart of backward, used by grad
+    grad_fn: Option<GradFn>,
+    // set once the closures above were dropped by a backward pass not retaining the graph
+    freed: bool,
+    // for Power nodes, so their data can be recomputed (see Value::recompute)
+    exponent: Option<f64>,
+    // for Custom nodes, the operation they were built by
+    custom: Option<Rc<dyn CustomOp>>,
+    // see Value::register_hook (shared, so they're called without keeping the core borrowed)
+    hooks: Vec<Rc<dyn Fn(f64) -> f64>>,
+    // non finite result found when the node was built in anomaly mode, returned by try_backward
+    anomaly: Option<Anomaly>,
+    // a leaf holding data rather than a parameter (see Value::constant), folded by CompiledGraph::simplify
+    constant: bool,
 }
 
 impl Debug for Core {
@@ -48,6 +172,13 @@ impl ValueConstructors for f64 {
                 op: None,
                 children: None,
                 backward: None,
+                grad_fn: None,
+                freed: false,
+                exponent: None,
+                custom: None,
+                hooks: Vec::new(),
+                anomaly: None,
+                constant: false,
             }))
         }
     }
@@ -63,6 +194,13 @@ impl ValueConstructors for (f64, f64) {
                 op: None,
                 children: None,
                 backward: None,
+                grad_fn: None,
+                freed: false,
+                exponent: None,
+                custom: None,
+                hooks: Vec::new(),
+                anomaly: None,
+                constant: false,
             }))
         }
     }
@@ -78,6 +216,13 @@ impl ValueConstructors for (f64, Option<Operation>) {
                 op: self.1,
                 children: None,
                 backward: None,
+                grad_fn: None,
+                freed: false,
+                exponent: None,
+                custom: None,
+                hooks: Vec::new(),
+                anomaly: None,
+                constant: false,
             }))
         }
     }
@@ -93,6 +238,13 @@ impl ValueConstructors for (f64, f64, Option<Operation>) {
                 op: self.2,
                 children: None,
                 backward: None,
+                grad_fn: None,
+                freed: false,
+                exponent: None,
+                custom: None,
+                hooks: Vec::new(),
+                anomaly: None,
+                constant: false,
             }))
         }
     }
@@ -108,6 +260,13 @@ impl ValueConstructors for (f64, Option<Operation>, Option<Vec<Value>>) {
                 op: self.1,
                 children: self.2,
                 backward: None,
+                grad_fn: None,
+                freed: false,
+                exponent: None,
+                custom: None,
+                hooks: Vec::new(),
+                anomaly: None,
+                constant: false,
             }))
         }
     }
@@ -123,13 +282,20 @@ impl ValueConstructors for (f64, f64, Option<Operation>, Option<Vec<Value>>) {
                 op: self.2,
                 children: self.3,
                 backward: None,
+                grad_fn: None,
+                freed: false,
+                exponent: None,
+                custom: None,
+                hooks: Vec::new(),
+                anomaly: None,
+                constant: false,
             }))
         }
     }
 }
 
 // constructor requiring fields: data, grad, op, children, backward
-impl ValueConstructors for (f64, f64, Option<Operation>, Option<Vec<Value>>, Option<Box<dyn Fn() -> ()>>) {
+impl ValueConstructors for (f64, f64, Option<Operation>, Option<Vec<Value>>, Option<Box<dyn Fn()>>) {
     fn construct(self) -> Value {
         Value {
             core: Rc::new(RefCell::new(Core {
@@ -138,11 +304,35 @@ impl ValueConstructors for (f64, f64, Option<Operation>, Option<Vec<Value>>, Opt
                 op: self.2,
                 children: self.3,
                 backward: self.4,
+                grad_fn: None,
+                freed: false,
+                exponent: None,
+                custom: None,
+                hooks: Vec::new(),
+                anomaly: None,
+                constant: false,
             }))
         }
     }
 }
 
+// operations a node type has to provide in order to be used as the building block of a nn::Model
+// (implemented both by Value and by the thread-safe SyncValue)
+pub trait Backend: Clone + Debug {
+    fn from_data(data: f64) -> Self;
+    // a leaf holding data rather than a parameter (e.g. an input or a literal)
+    fn constant(data: f64) -> Self {
+        Self::from_data(data)
+    }
+    fn get_data(&self) -> f64;
+    fn set_data(&self, val: f64);
+    fn get_grad(&self) -> f64;
+    fn set_grad(&self, val: f64);
+    fn power(&self, exp: f64) -> Self;
+    fn relu(&self) -> Self;
+    fn backward(&self);
+}
+
 #[derive(Clone, Debug)]
 pub struct Value {
     pub core: Rc<RefCell<Core>>,
@@ -155,49 +345,296 @@ impl Value {
         args.construct()
     }
 
+    // panics where try_backward returns an error
     pub fn backward(&self) {
-        let mut tp_order: Vec<Value> = vec![];
-        let mut visited = HashSet::new();
+        self.backward_with(BackwardOptions::default())
+    }
 
-        fn topological_sort(node: &Value, visited: &mut HashSet<Value>, tp_order: &mut Vec<Value>) {
-            if !visited.contains(&node) {
-                visited.insert(node.clone());
+    // panics where try_backward_with returns an error
+    pub fn backward_with(&self, options: BackwardOptions) {
+        if let Err(e) = self.try_backward_with(options) {
+            panic!("{}", e);
+        }
+    }
 
-                match node.core.borrow().children.as_ref() {
-                    Some(v) => {
-                        for c in v.iter() {
-                            topological_sort(c, visited, tp_order);
-                        }
-                        tp_order.push(node.clone());
-                    },
-                    None => {}
-                }
+    pub fn try_backward(&self) -> Result<(), OxiError> {
+        self.try_backward_with(BackwardOptions::default())
+    }
+
+    // on errors the gradients of the nodes already visited are left updated
+    pub fn try_backward_with(&self, options: BackwardOptions) -> Result<(), OxiError> {
+        // topological sort of graph's nodes
+        let (tp_order, leaves) = self.graph()?;
+
+        // checking the whole graph first, so a freed one or a forward anomaly is reported before any grad
+        // is touched (the first in topological order being where the non finite data showed up)
+        for v in leaves.iter().chain(tp_order.iter()) {
+            let core = v.core.try_borrow().map_err(|_| OxiError::Borrow)?;
+            if core.freed {
+                return Err(OxiError::GraphFreed { op: core.op });
+            }
+            if let Some(a) = core.anomaly.clone() {
+                return Err(self.locate(v, OxiError::Anomaly(a)));
             }
         }
 
-        // topological sort of graph's nodes
-        topological_sort(self, &mut visited, &mut tp_order);
+        // leaves (e.g. parameters) are left to accumulate, see Base::zero_grad and Value::zero_grad
+        if options.zero_grad {
+            for v in tp_order.iter() {
+                v.set_grad(0.0);
+            }
+        }
         
+        self.sweep(&tp_order, &leaves)?;
+
+        if !options.retain_graph {
+            for v in tp_order.iter() {
+                let mut core = v.core.try_borrow_mut().map_err(|_| OxiError::Borrow)?;
+                core.backward = None;
+                core.grad_fn = None;
+                core.freed = true;
+            }
+        }
+
+        Ok(())
+    }
+
+    // the backward pass proper from this node, given the nodes with children in topological order and the
+    // leaves they reach (see Value::graph), firing the hooks of the nodes it goes through
+    #[allow(clippy::mutable_key_type)]
+    pub(crate) fn sweep(&self, order: &[Value], leaves: &[Value]) -> Result<(), OxiError> {
+        // grads before the pass, so hooks only get what the pass adds (the starting node's one is set)
+        let hooked: HashMap<Value, f64> = order.iter()
+            .chain(leaves)
+            .filter(|v| !v.core.borrow().hooks.is_empty())
+            .map(|v| (v.clone(), if v == self { 0.0 } else { v.get_grad() }))
+            .collect();
+
         // a derivative of something (i.e. the starting node for the backward pass) w.r.t itself is 1
-        self.set_grad(1.0);
-
-        // backward pass on reversed topological order
-        for v in tp_order.iter().rev() {
-            match v.core.borrow_mut().backward.as_mut() {
-                Some(back) => back(),
-                None => {
-                    panic!("No backward closure to call for this node");
+        self.core.try_borrow().map_err(|_| OxiError::Borrow)?.grad.set(1.0);
+
+        // backward pass on reversed topological order, a node's grad is complete once reached
+        for v in order.iter().rev() {
+            if let Some(before) = hooked.get(v) {
+                v.fire_hooks(*before).map_err(|e| self.locate(v, e))?;
+            }
+            v.propagate().map_err(|e| self.locate(v, e))?;
+        }
+        for v in leaves.iter() {
+            if let Some(before) = hooked.get(v) {
+                v.fire_hooks(*before).map_err(|e| self.locate(v, e))?;
+            }
+        }
+
+        Ok(())
+    }
+
+    // hooks can register or clear hooks on this node, taking effect from the next pass
+    // in anomaly mode a hook returning a non finite gradient from a finite one is reported as the anomaly
+    fn fire_hooks(&self, before: f64) -> Result<(), OxiError> {
+        let (cell, hooks) = {
+            let core = self.core.try_borrow().map_err(|_| OxiError::Borrow)?;
+            (core.grad.clone(), core.hooks.clone())
+        };
+        let mut grad = cell.get() - before;
+        for hook in hooks.iter() {
+            let g = hook(grad);
+            if is_anomaly_enabled() && !g.is_finite() && grad.is_finite() {
+                return Err(OxiError::Anomaly(Anomaly {
+                    pass: Pass::Backward,
+                    op: format!("hook on {}", self.op_name()),
+                    inputs: vec![grad],
+                    value: g,
+                    path: vec![],
+                }));
+            }
+            grad = g;
+        }
+        cell.set(before + grad);
+
+        Ok(())
+    }
+
+    // hooks fire during each backward pass going through this node, in the order they were registered,
+    // with the gradient the node received in that pass (once all of it arrived, before it's propagated
+    // further) and return the one to use instead, e.g. |g| g to observe it, |g| -g to reverse it or
+    // |g| g.clamp(-1.0, 1.0) to clip it
+    pub fn register_hook<F>(&self, hook: F)
+        where F: Fn(f64) -> f64 + 'static
+    {
+        self.core.borrow_mut().hooks.push(Rc::new(hook));
+    }
+
+    pub fn clear_hooks(&self) {
+        self.core.borrow_mut().hooks.clear();
+    }
+
+    pub(crate) fn has_hooks(&self) -> bool {
+        !self.core.borrow().hooks.is_empty()
+    }
+
+    // calls the backward closure of a node with children, i.e. a step of the backward pass
+    pub(crate) fn propagate(&self) -> Result<(), OxiError> {
+        let core = self.core.try_borrow().map_err(|_| OxiError::Borrow)?;
+        if core.freed {
+            return Err(OxiError::GraphFreed { op: core.op });
+        }
+        let children = core.children.as_deref().unwrap_or_default();
+        // grads already non finite (e.g. left by a previous pass) aren't this step's anomaly
+        let before: Option<Vec<f64>> = is_anomaly_enabled().then(|| children.iter().map(|c| c.get_grad()).collect());
+        match (core.custom.as_ref(), core.backward.as_ref()) {
+            // custom operations are called here rather than by a closure, so that their gradients are checked
+            (Some(custom), _) => {
+                let data: Vec<f64> = children.iter().map(|c| c.get_data()).collect();
+                for (c, d) in children.iter().zip(custom_backward(custom.as_ref(), &data, core.grad.get())?) {
+                    c.set_grad(c.get_grad() + d);
+                }
+            },
+            (None, Some(back)) => back(),
+            (None, None) => return Err(OxiError::MissingBackward { op: core.op }),
+        }
+
+        if let Some(before) = before {
+            let grad = children.iter()
+                .zip(before)
+                .map(|(c, b)| (c.get_grad(), b))
+                .find(|(g, b)| !g.is_finite() && b.is_finite());
+            if let Some((grad, _)) = grad {
+                return Err(OxiError::Anomaly(Anomaly {
+                    pass: Pass::Backward,
+                    op: self.op_name(),
+                    inputs: children.iter().map(|c| c.get_data()).collect(),
+                    value: grad,
+                    path: vec![],
+                }));
+            }
+        }
+
+        Ok(())
+    }
+
+    // fills in the path of an anomaly found at node while backpropagating from this one
+    pub(crate) fn locate(&self, node: &Value, e: OxiError) -> OxiError {
+        match e {
+            OxiError::Anomaly(a) => OxiError::Anomaly(Anomaly { path: self.path_to(node), ..a }),
+            e => e,
+        }
+    }
+
+    // names of the operations from this node down to the given one (both included), empty if unreachable
+    #[allow(clippy::mutable_key_type)]
+    fn path_to(&self, node: &Value) -> Vec<String> {
+        fn search(from: &Value, to: &Value, visited: &mut HashSet<Value>, path: &mut Vec<Value>) -> bool {
+            path.push(from.clone());
+            if from == to {
+                return true;
+            }
+            if visited.insert(from.clone()) {
+                let children = from.core.borrow().children.clone().unwrap_or_default();
+                for c in children.iter() {
+                    if search(c, to, visited, path) {
+                        return true;
+                    }
+                }
+            }
+            path.pop();
+            false
+        }
+
+        let mut path = vec![];
+        search(self, node, &mut HashSet::new(), &mut path);
+
+        path.iter().map(|v| v.op_name()).collect()
+    }
+
+    // sets the data of a node with children from the current data of its children, i.e. a step of the
+    // forward pass (the backward closures read the data cells, so they stay valid)
+    pub(crate) fn recompute(&self) -> Result<(), OxiError> {
+        let core = self.core.try_borrow().map_err(|_| OxiError::Borrow)?;
+        let children = match core.children.as_ref() {
+            Some(children) => children,
+            None => return Ok(()),
+        };
+        let child = |i: usize| children.get(i).map(|c| c.get_data()).ok_or(OxiError::NotReplayable { op: core.op });
+
+        let data = match (core.op, core.exponent) {
+            (Some(Operation::Addition), _) => child(0)? + child(1)?,
+            (Some(Operation::Subtraction), _) => child(0)? - child(1)?,
+            (Some(Operation::Multiplication), _) => child(0)? * child(1)?,
+            (Some(Operation::Power), Some(exp)) => child(0)?.powf(exp),
+            (Some(Operation::ReLU), _) => if child(0)? >= 0.0 { child(0)? } else { 0.0 },
+            (Some(Operation::StopGradient), _) => child(0)?,
+            (Some(Operation::Custom), _) => match core.custom.as_ref() {
+                Some(custom) => custom.forward(&children.iter().map(|c| c.get_data()).collect::<Vec<f64>>()),
+                None => return Err(OxiError::NotReplayable { op: core.op }),
+            },
+            (op, _) => return Err(OxiError::NotReplayable { op }),
+        };
+        if is_anomaly_enabled() && !data.is_finite() {
+            return Err(OxiError::Anomaly(Anomaly {
+                pass: Pass::Forward,
+                op: self.op_name(),
+                inputs: children.iter().map(|c| c.get_data()).collect(),
+                value: data,
+                path: vec![],
+            }));
+        }
+        core.data.set(data);
+
+        Ok(())
+    }
+
+    // nodes with children reachable from this one, each after its children (leaves are left out)
+    pub(crate) fn topological_order(&self) -> Result<Vec<Value>, OxiError> {
+        Ok(self.graph()?.0)
+    }
+
+    // the topological order above and, apart, the leaves reachable from this node (itself if it's one)
+    // nodes are hashed by pointer, so their interior mutability doesn't affect the visited set
+    #[allow(clippy::mutable_key_type)]
+    pub(crate) fn graph(&self) -> Result<(Vec<Value>, Vec<Value>), OxiError> {
+        let mut tp_order: Vec<Value> = vec![];
+        let mut leaves: Vec<Value> = vec![];
+        let mut visited = HashSet::new();
+
+        fn topological_sort(
+            node: &Value,
+            visited: &mut HashSet<Value>,
+            tp_order: &mut Vec<Value>,
+            leaves: &mut Vec<Value>,
+        ) -> Result<(), OxiError> {
+            if !visited.contains(node) {
+                visited.insert(node.clone());
+
+                if let Some(v) = node.core.try_borrow().map_err(|_| OxiError::Borrow)?.children.as_ref() {
+                    for c in v.iter() {
+                        topological_sort(c, visited, tp_order, leaves)?;
+                    }
+                    tp_order.push(node.clone());
+                } else {
+                    leaves.push(node.clone());
                 }
             }
+            Ok(())
         }
+
+        topological_sort(self, &mut visited, &mut tp_order, &mut leaves)?;
+
+        Ok((tp_order, leaves))
     }
 
     pub fn power(&self, exp: f64) -> Self {
+        let data = self.get_data().powf(exp);
+        let anomaly = check_forward("Power", &[self], data);
+        if !is_grad_enabled() {
+            return Value::new((data, Some(Operation::Power))).with_anomaly(anomaly);
+        }
         let out = Value::new((
-            self.get_data().powf(exp),
+            data,
             Some(Operation::Power),
             Some(vec![self.clone()]),
         ));
+        out.core.borrow_mut().exponent = Some(exp);
 
         let  s_grad = self.core.borrow().grad.clone();
         let out_grad = out.core.borrow().grad.clone();
@@ -209,11 +646,19 @@ impl Value {
         });
         out.core.borrow_mut().backward = Some(back);
 
-        out
+        let child = self.clone();
+        let grad_fn = Box::new(move |g: &Value| vec![g * &(&child.power(exp - 1.0) * &Value::new(exp))]);
+        out.core.borrow_mut().grad_fn = Some(grad_fn);
+
+        out.with_anomaly(anomaly)
     }
 
     pub fn relu(&self) -> Self {
         let data = if self.get_data() >= 0.0 { self.get_data() } else { 0.0 };
+        let anomaly = check_forward("ReLU", &[self], data);
+        if !is_grad_enabled() {
+            return Value::new((data, Some(Operation::ReLU))).with_anomaly(anomaly);
+        }
         let out = Value::new((
             data,
             Some(Operation::ReLU),
@@ -230,14 +675,158 @@ impl Value {
         });
         out.core.borrow_mut().backward = Some(back);
 
-        out
+        let child = self.clone();
+        let grad_fn = Box::new(move |g: &Value| vec![g * &Value::new(if child.get_data() < 0.0 { 0.0 } else { 1.0 })]);
+        out.core.borrow_mut().grad_fn = Some(grad_fn);
+
+        out.with_anomaly(anomaly)
+    }
+
+    // a leaf that's known not to be a parameter (e.g. a literal), which CompiledGraph::simplify can fold
+    pub fn constant(data: f64) -> Self {
+        let v = Value::new(data);
+        v.core.borrow_mut().constant = true;
+        v
+    }
+
+    pub fn is_constant(&self) -> bool {
+        self.core.borrow().constant
+    }
+
+    // a new leaf holding the current data, i.e. a constant w.r.t. the graph this node belongs to
+    pub fn detach(&self) -> Self {
+        Value::constant(self.get_data())
+    }
+
+    // passes the data forward but blocks gradients backward (e.g. for straight-through estimators
+    // or truncated backprop), unlike detach the node keeps its child so the graph stays whole
+    pub fn stop_gradient(&self) -> Self {
+        let anomaly = check_forward("StopGradient", &[self], self.get_data());
+        if !is_grad_enabled() {
+            return Value::new((self.get_data(), Some(Operation::StopGradient))).with_anomaly(anomaly);
+        }
+        let out = Value::new((
+            self.get_data(),
+            Some(Operation::StopGradient),
+            Some(vec![self.clone()]),
+        ));
+
+        // derivative for stop gradient operation (nothing is propagated to the child)
+        let back = Box::new(|| {});
+        out.core.borrow_mut().backward = Some(back);
+        out.core.borrow_mut().grad_fn = Some(Box::new(|_| vec![Value::new(0.0)]));
+
+        out.with_anomaly(anomaly)
+    }
+
+    // a - b as a single node, rather than a + b * -1 as built by the Sub operator (see CompiledGraph::simplify)
+    pub(crate) fn subtraction(&self, other: &Value) -> Self {
+        let data = self.get_data() - other.get_data();
+        let anomaly = check_forward("Subtraction", &[self, other], data);
+        if !is_grad_enabled() {
+            return Value::new((data, Some(Operation::Subtraction))).with_anomaly(anomaly);
+        }
+        let out = Value::new((
+            data,
+            Some(Operation::Subtraction),
+            Some(vec![self.clone(), other.clone()]),
+        ));
+
+        let s_grad = self.core.borrow().grad.clone();
+        let oth_grad = other.core.borrow().grad.clone();
+        let out_grad = out.core.borrow().grad.clone();
+
+        // derivative for sub operation
+        let back = Box::new(move || {
+            s_grad.set(s_grad.get() + out_grad.get());
+            oth_grad.set(oth_grad.get() - out_grad.get());
+        });
+        out.core.borrow_mut().backward = Some(back);
+        out.core.borrow_mut().grad_fn = Some(Box::new(|g| vec![g.clone(), -g]));
+
+        out.with_anomaly(anomaly)
+    }
+
+    // a node computed by a custom operation on the given inputs, e.g. Value::custom(Rc::new(Sigmoid), &[x]);
+    // custom operations are differentiated once: try_grad with create_graph returns an error on these nodes
+    pub fn custom(op: Rc<dyn CustomOp>, inputs: &[Value]) -> Self {
+        let data = op.forward(&inputs.iter().map(|i| i.get_data()).collect::<Vec<f64>>());
+        let anomaly = check_forward(op.name(), &inputs.iter().collect::<Vec<&Value>>(), data);
+        let out = if is_grad_enabled() {
+            Value::new((data, Some(Operation::Custom), Some(inputs.to_vec())))
+        } else {
+            Value::new((data, Some(Operation::Custom)))
+        };
+        // no closures: the operation's own backward is called by propagate and try_grad
+        out.core.borrow_mut().custom = Some(op);
+
+        out.with_anomaly(anomaly)
+    }
+
+    fn with_anomaly(self, anomaly: Option<Anomaly>) -> Self {
+        if anomaly.is_some() {
+            self.core.borrow_mut().anomaly = anomaly;
+        }
+        self
+    }
+
+    pub fn op(&self) -> Option<Operation> {
+        self.core.borrow().op
+    }
+
+    // name of the operation as shown in graph dumps, i.e. the one of the custom operation for Custom nodes
+    pub fn op_name(&self) -> String {
+        let core = self.core.borrow();
+        match (core.op, core.custom.as_ref()) {
+            (_, Some(custom)) => custom.name().to_string(),
+            (Some(op), None) => format!("{:?}", op),
+            (None, None) => "Leaf".to_string(),
+        }
+    }
+
+    pub(crate) fn custom_op(&self) -> Option<Rc<dyn CustomOp>> {
+        self.core.borrow().custom.clone()
+    }
+
+    // text dump of the graph this node is the output of, a line per node with its inputs, children first,
+    // e.g. "#2 = Multiplication(#0, #1) data=6 grad=1"
+    #[allow(clippy::mutable_key_type)]
+    pub fn dump(&self) -> String {
+        fn visit(node: &Value, ids: &mut HashMap<Value, usize>, lines: &mut Vec<String>) {
+            if ids.contains_key(node) {
+                return;
+            }
+            let children = node.core.borrow().children.clone().unwrap_or_default();
+            for c in children.iter() {
+                visit(c, ids, lines);
+            }
+
+            let id = ids.len();
+            ids.insert(node.clone(), id);
+            let line = if children.is_empty() {
+                format!("#{} = {}", id, node.op_name())
+            } else {
+                let args: Vec<String> = children.iter().map(|c| format!("#{}", ids[c])).collect();
+                format!("#{} = {}({})", id, node.op_name(), args.join(", "))
+            };
+            lines.push(format!("{} data={} grad={}", line, node.get_data(), node.get_grad()));
+        }
+
+        let mut lines = vec![];
+        visit(self, &mut HashMap::new(), &mut lines);
+
+        lines.join("\n")
+    }
+
+    pub(crate) fn exponent(&self) -> Option<f64> {
+        self.core.borrow().exponent
     }
 
     pub fn get_data(&self) -> f64 {
         self.core.borrow().data.get()
     }
 
-    pub fn set_data(&self, val: f64) -> () {
+    pub fn set_data(&self, val: f64) {
         self.core.borrow().data.set(val);
     }
 
@@ -245,10 +834,132 @@ impl Value {
         self.core.borrow().grad.get()
     }
 
-    pub fn set_grad(&self, val: f64) -> () {
+    pub fn set_grad(&self, val: f64) {
         self.core.borrow().grad.set(val);
     }
 
+    // resets the grad of this node and of every node it depends on, leaves included
+    // (unlike Base::zero_grad, which only resets the parameters of a model)
+    #[allow(clippy::mutable_key_type)]
+    pub fn zero_grad(&self) {
+        let mut visited = HashSet::new();
+        let mut stack = vec![self.clone()];
+
+        while let Some(node) = stack.pop() {
+            if visited.insert(node.clone()) {
+                let core = node.core.borrow();
+                core.grad.set(0.0);
+                if let Some(children) = core.children.as_ref() {
+                    stack.extend(children.iter().cloned());
+                }
+            }
+        }
+    }
+
+}
+
+// gradients of a custom operation, checked to be one per input (a missing one would be silently dropped)
+fn custom_backward(op: &dyn CustomOp, inputs: &[f64], grad: f64) -> Result<Vec<f64>, OxiError> {
+    let grads = op.backward(inputs, grad);
+    if grads.len() != inputs.len() {
+        return Err(OxiError::CustomGradients { op: op.name().to_string(), expected: inputs.len(), found: grads.len() });
+    }
+
+    Ok(grads)
+}
+
+// HIGHER ORDER DERIVATIVES
+// panics where try_grad returns an error
+pub fn grad(output: &Value, inputs: &[Value], create_graph: bool) -> Vec<Value> {
+    match try_grad(output, inputs, create_graph) {
+        Ok(grads) => grads,
+        Err(e) => panic!("{}", e),
+    }
+}
+
+// gradients of output w.r.t. each of the inputs (zero for inputs it doesn't depend on) returned as nodes,
+// with create_graph they're built out of operations on Values so they can be differentiated in turn
+// (e.g. Hessian-vector products, gradient penalties, Newton's method), otherwise they're plain leaves;
+// unlike backward the grads of the nodes are left untouched
+// custom operations can't be part of a created graph, as their gradients are only known as numbers
+#[allow(clippy::mutable_key_type)]
+pub fn try_grad(output: &Value, inputs: &[Value], create_graph: bool) -> Result<Vec<Value>, OxiError> {
+    let tp_order = output.topological_order()?;
+    let _guard = if create_graph { None } else { Some(no_grad()) };
+
+    // gradient of the output w.r.t. each node met so far, accumulated over the paths reaching it
+    let mut grads: HashMap<Value, Value> = HashMap::new();
+    grads.insert(output.clone(), Value::new(1.0));
+
+    for v in tp_order.iter().rev() {
+        let g = match grads.get(v) {
+            Some(g) => g.clone(),
+            None => continue,
+        };
+        let core = v.core.try_borrow().map_err(|_| OxiError::Borrow)?;
+        if core.freed {
+            return Err(OxiError::GraphFreed { op: core.op });
+        }
+        let children = core.children.as_deref().unwrap_or_default();
+        let derivatives = match (core.custom.as_ref(), core.grad_fn.as_ref()) {
+            (Some(custom), _) if create_graph => return Err(OxiError::NotTwiceDifferentiable { op: custom.name().to_string() }),
+            // without create_graph the upstream gradient is a plain number
+            (Some(custom), _) => {
+                let data: Vec<f64> = children.iter().map(|c| c.get_data()).collect();
+                custom_backward(custom.as_ref(), &data, g.get_data())?.into_iter().map(Value::new).collect()
+            },
+            (None, Some(grad_fn)) => grad_fn(&g),
+            (None, None) => return Err(OxiError::MissingBackward { op: core.op }),
+        };
+
+        for (c, d) in children.iter().zip(derivatives) {
+            let acc = match grads.remove(c) {
+                Some(prev) => &prev + &d,
+                None => d,
+            };
+            grads.insert(c.clone(), acc);
+        }
+    }
+
+    Ok(inputs.iter().map(|i| grads.get(i).cloned().unwrap_or_else(|| Value::new(0.0))).collect())
+}
+
+impl Backend for Value {
+    fn from_data(data: f64) -> Self {
+        Value::new(data)
+    }
+
+    fn constant(data: f64) -> Self {
+        Value::constant(data)
+    }
+
+    fn get_data(&self) -> f64 {
+        Value::get_data(self)
+    }
+
+    fn set_data(&self, val: f64) {
+        Value::set_data(self, val)
+    }
+
+    fn get_grad(&self) -> f64 {
+        Value::get_grad(self)
+    }
+
+    fn set_grad(&self, val: f64) {
+        Value::set_grad(self, val)
+    }
+
+    fn power(&self, exp: f64) -> Self {
+        Value::power(self, exp)
+    }
+
+    fn relu(&self) -> Self {
+        Value::relu(self)
+    }
+
+    fn backward(&self) {
+        Value::backward(self)
+    }
 }
 
 impl PartialEq for Value {
@@ -269,8 +980,13 @@ impl ops::Add<&Value> for &Value {
     type Output = Value;
 
     fn add(self, other: &Value) -> Self::Output {
+        let data = self.get_data() + other.get_data();
+        let anomaly = check_forward("Addition", &[self, other], data);
+        if !is_grad_enabled() {
+            return Value::new((data, Some(Operation::Addition))).with_anomaly(anomaly);
+        }
         let out = Value::new((
-            self.get_data() + other.get_data(),
+            data,
             Some(Operation::Addition),
             Some(vec![self.clone(), other.clone()]),
         ));
@@ -283,10 +999,11 @@ impl ops::Add<&Value> for &Value {
         let back = Box::new(move || {
             s_grad.set(s_grad.get() + out_grad.get());
             oth_grad.set(oth_grad.get() + out_grad.get());
-        }) as Box<dyn Fn() -> ()>;
+        }) as Box<dyn Fn()>;
         out.core.borrow_mut().backward = Some(back);
+        out.core.borrow_mut().grad_fn = Some(Box::new(|g| vec![g.clone(), g.clone()]));
 
-        out
+        out.with_anomaly(anomaly)
     }
 }
 
@@ -310,7 +1027,7 @@ impl ops::Add<f64> for &Value {
     type Output = Value;
 
     fn add(self, other: f64) -> Self::Output {
-        self + Value::new(other)
+        self + Value::constant(other)
     }
 }
 
@@ -318,7 +1035,7 @@ impl ops::Add<f64> for &Value {
 impl<'a> std::iter::Sum<&'a Value> for Value {
     fn sum<I: Iterator<Item = &'a Value>>(iter: I) -> Self {
         iter.fold(
-            Value::new(0.0), 
+            Value::constant(0.0), 
             |sum, el| sum + el,
         )
     }
@@ -327,9 +1044,16 @@ impl<'a> std::iter::Sum<&'a Value> for Value {
 impl ops::Mul<&Value> for &Value {
     type Output = Value;
 
+    // the backward closure accumulates gradients, hence the additions
+    #[allow(clippy::suspicious_arithmetic_impl)]
     fn mul(self, other: &Value) -> Self::Output {
+        let data = self.get_data() * other.get_data();
+        let anomaly = check_forward("Multiplication", &[self, other], data);
+        if !is_grad_enabled() {
+            return Value::new((data, Some(Operation::Multiplication))).with_anomaly(anomaly);
+        }
         let out = Value::new((
-            self.get_data() * other.get_data(),
+            data,
             Some(Operation::Multiplication),
             Some(vec![self.clone(), other.clone()]),
         ));
@@ -345,10 +1069,14 @@ impl ops::Mul<&Value> for &Value {
         let back = Box::new(move || {
             s_grad.set(s_grad.get() + (oth_data.get() * out_grad.get()));
             oth_grad.set(oth_grad.get() + (s_data.get() * out_grad.get()));
-        }) as Box<dyn Fn() -> ()>;
+        }) as Box<dyn Fn()>;
         out.core.borrow_mut().backward = Some(back);
 
-        out
+        let (s, oth) = (self.clone(), other.clone());
+        let grad_fn = Box::new(move |g: &Value| vec![g * &oth, g * &s]);
+        out.core.borrow_mut().grad_fn = Some(grad_fn);
+
+        out.with_anomaly(anomaly)
     }
 }
 
@@ -372,7 +1100,7 @@ impl ops::Neg for &Value {
     type Output = Value;
 
     fn neg(self) -> Self::Output {
-        self * &Value::new(-1.0)
+        self * &Value::constant(-1.0)
     }
 }
 
@@ -380,7 +1108,7 @@ impl ops::Neg for Value {
     type Output = Value;
 
     fn neg(self) -> Self::Output {
-        self * &Value::new(-1.0)
+        self * &Value::constant(-1.0)
     }
 }
 
@@ -404,7 +1132,7 @@ impl ops::Div<f64> for Value {
     type Output = Value;
 
     fn div(self, other: f64) -> Self::Output {
-        self * &Value::new(1.0/other)
+        self * &Value::constant(1.0/other)
     }
 }
 
@@ -427,6 +1155,8 @@ impl Display for Value {
 }
 
 #[cfg(test)]
+// the tests predating the lints are kept as written
+#[allow(clippy::toplevel_ref_arg, clippy::approx_constant)]
 mod test {
     use super::*;
 
@@ -595,4 +1325,451 @@ mod test {
         assert_eq!(v.get_data(), 1.2345);
         assert_eq!(v.get_grad(), 6.7890);
     }
+
+    #[test]
+    fn test_try_backward() {
+        let a = Value::new(2.0);
+        let b = &a * &Value::new(3.0);
+        assert!(b.try_backward().is_ok());
+        assert_eq!(a.get_grad(), 3.0);
+
+        // children without a backward closure
+        let c = Value::new((1.0, Some(Operation::Addition), Some(vec![a.clone()])));
+        assert!(matches!(c.try_backward(), Err(OxiError::MissingBackward { op: Some(Operation::Addition) })));
+
+        // a node borrowed elsewhere while traversing the graph
+        let _guard = a.core.borrow_mut();
+        assert!(matches!(b.try_backward(), Err(OxiError::Borrow)));
+    }
+
+    #[test]
+    fn test_no_grad() {
+        let a = Value::new(2.0);
+        let b = Value::new(-3.0);
+        {
+            let _guard = no_grad();
+            assert!(!is_grad_enabled());
+            let c = (&a * &b + &a).relu() + &(&a.power(2.0) / &b);
+
+            // data only, no graph to backpropagate through
+            assert_eq!((c.get_data() * 1_000_f64).round() / 1_000_f64, -1.333);
+            assert!(c.core.borrow().children.is_none());
+            c.backward();
+            assert_eq!(a.get_grad(), 0.0);
+
+            // nested guards restore the mode they found
+            {
+                let _inner = no_grad();
+            }
+            assert!(!is_grad_enabled());
+        }
+        assert!(is_grad_enabled());
+
+        let c = &a * &b;
+        c.backward();
+        assert_eq!(a.get_grad(), -3.0);
+    }
+
+    #[test]
+    fn test_detach() {
+        let a = Value::new(3.0);
+        let b = &a * &a;
+        let c = b.detach();
+        let d = &c * &a;
+        d.backward();
+
+        assert_eq!(c.get_data(), 9.0);
+        assert!(c.core.borrow().children.is_none());
+        // b is treated as a constant, so only the direct use of a counts
+        assert_eq!(a.get_grad(), 9.0);
+        assert_eq!(b.get_grad(), 0.0);
+    }
+
+    #[test]
+    fn test_stop_gradient() {
+        let a = Value::new(2.0);
+        let b = Value::new(5.0);
+        let c = &(&a * &b).stop_gradient() + &b;
+        c.backward();
+
+        assert!(matches!(c.core.borrow().children.as_ref().unwrap()[0].core.borrow().op, Some(Operation::StopGradient)));
+        assert_eq!(c.get_data(), 15.0);
+        assert_eq!(a.get_grad(), 0.0);
+        assert_eq!(b.get_grad(), 1.0);
+
+        // straight-through estimator: rounds forward, identity backward
+        let x = Value::new(0.7);
+        let q = &x + &(&Value::new(x.get_data().round()) - &x).stop_gradient();
+        q.backward();
+        assert_eq!(q.get_data(), 1.0);
+        assert_eq!(x.get_grad(), 1.0);
+    }
+
+    #[test]
+    fn test_grad() {
+        let x = Value::new(2.0);
+        let y = Value::new(3.0);
+        let z = Value::new(4.0);
+        let f = &(&x.power(3.0) * &y) + &(&y / &x);
+
+        // df/dx = 3x^2y - y/x^2, df/dy = x^3 + 1/x, f doesn't depend on z
+        let g = grad(&f, &[x.clone(), y.clone(), z.clone()], false);
+        assert_eq!(g.iter().map(|v| v.get_data()).collect::<Vec<f64>>(), vec![35.25, 8.5, 0.0]);
+        assert!(g[0].core.borrow().children.is_none());
+        // same results as backward, whose grads are left untouched
+        assert_eq!(x.get_grad(), 0.0);
+        f.backward();
+        assert_eq!((x.get_grad(), y.get_grad()), (35.25, 8.5));
+    }
+
+    #[test]
+    fn test_grad_of_grad() {
+        let x = Value::new(2.0);
+        let y = Value::new(3.0);
+        let f = &(&x.power(3.0) * &y) + &(&x * &x).relu();
+
+        // d2f/dx2 = 6xy + 2, d2f/dxdy = 3x^2
+        let dx = &grad(&f, &[x.clone(), y.clone()], true)[0];
+        assert_eq!(dx.get_data(), 40.0);
+        let h = grad(dx, &[x.clone(), y.clone()], true);
+        assert_eq!((h[0].get_data(), h[1].get_data()), (38.0, 12.0));
+
+        // third order, d3f/dx3 = 6y
+        assert_eq!(grad(&h[0], std::slice::from_ref(&x), false)[0].get_data(), 18.0);
+
+        // gradients through stop_gradient are zero at every order
+        let s = &x.stop_gradient() * &x;
+        let ds = &grad(&s, std::slice::from_ref(&x), true)[0];
+        assert_eq!(ds.get_data(), 2.0);
+        assert_eq!(grad(ds, std::slice::from_ref(&x), false)[0].get_data(), 0.0);
+    }
+
+    #[test]
+    fn test_newton() {
+        // minimizing f(x) = (x - 3)^4 + x^2 with Newton's method, x <- x - f'(x)/f''(x)
+        let mut x = 0.0;
+        for _ in 0..20 {
+            let v = Value::new(x);
+            let f = &(&v + -3.0).power(4.0) + &v.power(2.0);
+            let d1 = &grad(&f, std::slice::from_ref(&v), true)[0];
+            let d2 = &grad(d1, std::slice::from_ref(&v), false)[0];
+            x -= d1.get_data() / d2.get_data();
+        }
+
+        // stationary point: 4(x - 3)^3 + 2x = 0
+        assert!((4.0 * (x - 3.0).powf(3.0) + 2.0 * x).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_zero_grad() {
+        let a = Value::new(2.0);
+        let b = &(&a * &a) + &Value::new(1.0);
+        b.backward();
+        assert_eq!(a.get_grad(), 4.0);
+
+        b.zero_grad();
+        assert_eq!((a.get_grad(), b.get_grad()), (0.0, 0.0));
+        // a second pass now gives the same grads instead of accumulating
+        b.backward();
+        assert_eq!(a.get_grad(), 4.0);
+    }
+
+    #[test]
+    fn test_backward_with() {
+        let a = Value::new(2.0);
+        let b = &a * &a;
+        let c = &b + &a;
+
+        // running twice accumulates into every node, and the intermediate grads feed the leaves again
+        c.backward();
+        c.backward();
+        assert_eq!((a.get_grad(), b.get_grad()), (14.0, 2.0));
+
+        // intermediate nodes are reset, leaves keep accumulating
+        c.backward_with(BackwardOptions { zero_grad: true, ..BackwardOptions::default() });
+        assert_eq!((a.get_grad(), b.get_grad()), (19.0, 1.0));
+
+        // a freed graph can't be backpropagated again, nor differentiated with grad
+        a.set_grad(0.0);
+        assert!(c.try_backward_with(BackwardOptions { zero_grad: true, retain_graph: false }).is_ok());
+        assert_eq!(a.get_grad(), 5.0);
+        assert!(c.core.borrow().backward.is_none());
+        assert!(matches!(c.try_backward(), Err(OxiError::GraphFreed { .. })));
+        assert!(matches!(try_grad(&c, std::slice::from_ref(&a), false), Err(OxiError::GraphFreed { .. })));
+        assert_eq!(a.get_grad(), 5.0);
+    }
+
+    struct Sigmoid;
+
+    impl CustomOp for Sigmoid {
+        fn name(&self) -> &str {
+            "sigmoid"
+        }
+
+        fn forward(&self, inputs: &[f64]) -> f64 {
+            1.0 / (1.0 + (-inputs[0]).exp())
+        }
+
+        fn backward(&self, inputs: &[f64], grad: f64) -> Vec<f64> {
+            let s = self.forward(inputs);
+            vec![s * (1.0 - s) * grad]
+        }
+    }
+
+    // a * b + c in a single node
+    struct Fma;
+
+    impl CustomOp for Fma {
+        fn name(&self) -> &str {
+            "fma"
+        }
+
+        fn forward(&self, inputs: &[f64]) -> f64 {
+            inputs[0].mul_add(inputs[1], inputs[2])
+        }
+
+        fn backward(&self, inputs: &[f64], grad: f64) -> Vec<f64> {
+            vec![inputs[1] * grad, inputs[0] * grad, grad]
+        }
+    }
+
+    #[test]
+    fn test_custom() {
+        let (a, b, c) = (Value::new(2.0), Value::new(-3.0), Value::new(4.0));
+        let f = Value::custom(Rc::new(Fma), &[a.clone(), b.clone(), c.clone()]);
+        let s = Value::custom(Rc::new(Sigmoid), std::slice::from_ref(&f));
+        let out = &s * &a;
+        out.backward();
+
+        assert_eq!(f.get_data(), -2.0);
+        assert_eq!(out.get_data(), 2.0 / (1.0 + 2.0_f64.exp()));
+        let ds = s.get_data() * (1.0 - s.get_data()) * 2.0;
+        assert_eq!((a.get_grad(), b.get_grad(), c.get_grad()), (s.get_data() + ds * -3.0, ds * 2.0, ds));
+
+        // named in dumps
+        let dump = out.dump();
+        assert!(dump.contains("#3 = fma(#0, #1, #2) data=-2"));
+        assert!(dump.contains("#4 = sigmoid(#3)"));
+        assert!(dump.lines().last().unwrap().starts_with("#5 = Multiplication(#4, #0)"));
+        assert_eq!(s.op_name(), "sigmoid");
+        assert!(matches!(s.op(), Some(Operation::Custom)));
+
+        // differentiable with grad (once) and replayable
+        assert_eq!(grad(&out, std::slice::from_ref(&c), false)[0].get_data(), c.get_grad());
+        assert!(matches!(
+            try_grad(&out, std::slice::from_ref(&c), true),
+            Err(OxiError::NotTwiceDifferentiable { op }) if op == "sigmoid"
+        ));
+        a.set_data(0.0);
+        f.recompute().unwrap();
+        s.recompute().unwrap();
+        assert_eq!(s.get_data(), 1.0 / (1.0 + (-4.0_f64).exp()));
+    }
+
+    // a gradient short
+    struct Broken;
+
+    impl CustomOp for Broken {
+        fn name(&self) -> &str {
+            "broken"
+        }
+
+        fn forward(&self, inputs: &[f64]) -> f64 {
+            inputs[0] + inputs[1]
+        }
+
+        fn backward(&self, _inputs: &[f64], grad: f64) -> Vec<f64> {
+            vec![grad]
+        }
+    }
+
+    #[test]
+    fn test_custom_gradient_count() {
+        let (a, b) = (Value::new(1.0), Value::new(2.0));
+        let out = Value::custom(Rc::new(Broken), &[a.clone(), b.clone()]);
+
+        let expected = |r: Result<_, OxiError>| matches!(
+            r,
+            Err(OxiError::CustomGradients { op, expected: 2, found: 1 }) if op == "broken"
+        );
+        assert!(expected(out.try_backward()));
+        assert!(expected(try_grad(&out, &[a, b], false).map(|_| ())));
+    }
+
+    // a backward that isn't linear in the upstream gradient
+    struct SquaredUpstream;
+
+    impl CustomOp for SquaredUpstream {
+        fn name(&self) -> &str {
+            "squared_upstream"
+        }
+
+        fn forward(&self, inputs: &[f64]) -> f64 {
+            inputs[0]
+        }
+
+        fn backward(&self, _inputs: &[f64], grad: f64) -> Vec<f64> {
+            vec![grad * grad]
+        }
+    }
+
+    #[test]
+    fn test_custom_upstream_gradient() {
+        let x = Value::new(1.0);
+        let out = &Value::custom(Rc::new(SquaredUpstream), std::slice::from_ref(&x)) * &Value::new(3.0);
+        out.backward();
+
+        assert_eq!(x.get_grad(), 9.0);
+        assert_eq!(grad(&out, std::slice::from_ref(&x), false)[0].get_data(), 9.0);
+    }
+
+    #[test]
+    fn test_anomaly_backward() {
+        // sqrt(x) has an infinite derivative in 0, which turns into a NaN through the 0 weight
+        let x = Value::new(0.0);
+        let w = Value::new(0.0);
+        let loss = &(&x.power(0.5) * &w) + &Value::new(1.0);
+
+        // silently propagated without anomaly detection
+        loss.backward();
+        assert!(x.get_grad().is_nan());
+
+        x.set_grad(0.0);
+        let _guard = detect_anomaly();
+        match loss.try_backward() {
+            Err(OxiError::Anomaly(a)) => {
+                assert_eq!(a.pass, Pass::Backward);
+                assert_eq!(a.op, "Power");
+                assert_eq!(a.inputs, vec![0.0]);
+                assert!(a.value.is_nan());
+                assert_eq!(a.path, vec!["Addition", "Multiplication", "Power"]);
+                assert!(OxiError::Anomaly(a).to_string().contains("Addition -> Multiplication -> Power"));
+            },
+            r => panic!("expected an anomaly, got {:?}", r),
+        }
+    }
+
+    #[test]
+    fn test_anomaly_backward_stale() {
+        // a grad that was already NaN before this pass isn't reported again
+        let x = Value::new(1.0);
+        x.set_grad(f64::NAN);
+        let y = Value::new(2.0);
+        let loss = &(&x * &y) + &y;
+
+        let _guard = detect_anomaly();
+        assert!(loss.try_backward().is_ok());
+        assert!(x.get_grad().is_nan());
+        assert_eq!(y.get_grad(), 2.0);
+
+        // while one turning non finite in this pass is
+        let z = Value::new(0.0);
+        assert!(matches!(z.power(0.5).try_backward(), Err(OxiError::Anomaly(a)) if a.op == "Power"));
+    }
+
+    #[test]
+    fn test_anomaly_forward() {
+        let x = Value::new(0.0);
+        // infinite, but allowed without anomaly detection
+        assert!(x.power(-1.0).get_data().is_infinite());
+
+        let guard = detect_anomaly();
+        assert!(is_anomaly_enabled());
+        // recorded on the node and returned by backward, with the path to the first non finite result
+        let w = Value::new(2.0);
+        let loss = &(&x.power(-1.0) * &w) + &Value::new(1.0);
+        assert!(loss.get_data().is_infinite());
+        match loss.try_backward() {
+            Err(OxiError::Anomaly(a)) => {
+                assert_eq!(a.pass, Pass::Forward);
+                assert_eq!(a.op, "Power");
+                assert_eq!(a.inputs, vec![0.0]);
+                assert!(a.value.is_infinite());
+                assert_eq!(a.path, vec!["Addition", "Multiplication", "Power"]);
+            },
+            r => panic!("expected an anomaly, got {:?}", r),
+        }
+        assert_eq!(w.get_grad(), 0.0);
+        drop(guard);
+        assert!(!is_anomaly_enabled());
+    }
+
+    #[test]
+    fn test_anomaly_hooks() {
+        let x = Value::new(2.0);
+        let h = &x * &x;
+        let loss = &h + &Value::new(1.0);
+        h.register_hook(|g| g / 0.0);
+
+        let _guard = detect_anomaly();
+        match loss.try_backward() {
+            Err(OxiError::Anomaly(a)) => {
+                assert_eq!(a.pass, Pass::Backward);
+                assert_eq!(a.op, "hook on Multiplication");
+                assert_eq!(a.inputs, vec![1.0]);
+                assert!(a.value.is_infinite());
+                assert_eq!(a.path, vec!["Addition", "Multiplication"]);
+            },
+            r => panic!("expected an anomaly, got {:?}", r),
+        }
+    }
+
+    #[test]
+    fn test_hooks() {
+        let w = Value::new(3.0);
+        let x = Value::new(2.0);
+        let h = &w * &x;
+        let out = &(&h * &h) + &w;
+
+        // observing: the intermediate node gets d(h^2)/dh = 2h once, after both of its uses
+        let seen = Rc::new(RefCell::new(vec![]));
+        let log = seen.clone();
+        h.register_hook(move |g| { log.borrow_mut().push(g); g });
+        out.backward();
+        assert_eq!(*seen.borrow(), vec![12.0]);
+        assert_eq!(w.get_grad(), 12.0 * 2.0 + 1.0);
+
+        // replacing: gradient reversal on the intermediate node reaches the leaves below it only
+        w.set_grad(0.0);
+        x.set_grad(0.0);
+        h.register_hook(|g| -g);
+        out.backward_with(BackwardOptions { zero_grad: true, ..BackwardOptions::default() });
+        assert_eq!(*seen.borrow(), vec![12.0, 12.0]);
+        assert_eq!((w.get_grad(), x.get_grad()), (-24.0 + 1.0, -36.0));
+
+        // leaves get only what the pass adds, while their grads keep accumulating
+        h.clear_hooks();
+        let leaf = Rc::new(RefCell::new(vec![]));
+        let log = leaf.clone();
+        w.register_hook(move |g| { log.borrow_mut().push(g); g.clamp(-10.0, 10.0) });
+        out.backward_with(BackwardOptions { zero_grad: true, ..BackwardOptions::default() });
+        assert_eq!(*leaf.borrow(), vec![25.0]);
+        assert_eq!(w.get_grad(), -23.0 + 10.0);
+
+        // on the starting node, whose own grad is 1
+        w.clear_hooks();
+        out.register_hook(|g| g * 2.0);
+        w.set_grad(0.0);
+        out.backward_with(BackwardOptions { zero_grad: true, ..BackwardOptions::default() });
+        assert_eq!((out.get_grad(), w.get_grad()), (2.0, 50.0));
+    }
+
+    #[test]
+    fn test_hooks_reentrant() {
+        // a hook replacing itself, the new one firing from the next pass
+        let a = Value::new(1.0);
+        let out = &a * &Value::new(3.0);
+        let node = a.clone();
+        a.register_hook(move |g| {
+            node.clear_hooks();
+            node.register_hook(|g| g * 10.0);
+            g * 2.0
+        });
+
+        out.backward();
+        assert_eq!(a.get_grad(), 6.0);
+        a.set_grad(0.0);
+        out.backward();
+        assert_eq!(a.get_grad(), 30.0);
+    }
 }
@@ -0,0 +1,133 @@
+use std::fmt::Display;
+use std::io;
+use super::engine::Operation;
+use super::dataset::CsvError;
+
+// CRATE ERROR
+// returned by the fallible (try_*) variants of the functions that would otherwise panic
+#[derive(Debug)]
+pub enum OxiError {
+    // a node with children but no closure to propagate its gradient (e.g. built by hand with Value::new)
+    MissingBackward { op: Option<Operation> },
+    // a node whose closures were freed by a backward pass that didn't retain the graph
+    GraphFreed { op: Option<Operation> },
+    // a NaN or an infinity found in anomaly mode (see engine::detect_anomaly)
+    Anomaly(Anomaly),
+    // a node whose data can't be recomputed from its children (e.g. built by hand with Value::new)
+    NotReplayable { op: Option<Operation> },
+    // a custom operation met by grad with create_graph (see Value::custom)
+    NotTwiceDifferentiable { op: String },
+    // a custom operation whose backward didn't return a gradient per input
+    CustomGradients { op: String, expected: usize, found: usize },
+    // a node's core is already mutably borrowed (e.g. re-entering the graph from a backward closure)
+    Borrow,
+    EmptyArchitecture,
+    EmptyLayer { layer: usize },
+    ActivationCount { layers: usize, activations: usize },
+    ZeroInputs,
+    DimensionMismatch { expected: usize, found: usize },
+    LengthMismatch { data: usize, labels: usize },
+    RowWidth { row: usize, expected: usize, found: usize },
+    InvalidK { k: usize, samples: usize },
+    InvalidRatios { validation: f64, test: f64 },
+    ClassCount { expected: usize, found: usize },
+    // a callback stopped the training because of an error (see callbacks::Control::Abort)
+    Aborted(String),
+    Csv(CsvError),
+    Io(io::Error),
+}
+
+pub type Result<T> = std::result::Result<T, OxiError>;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Pass {
+    Forward,
+    Backward,
+}
+
+// where a non finite number showed up first: the operation, the data of its inputs and either its result
+// (forward) or the gradient it propagated to one of its inputs (backward)
+#[derive(Clone, Debug, PartialEq)]
+pub struct Anomaly {
+    pub pass: Pass,
+    pub op: String,
+    pub inputs: Vec<f64>,
+    pub value: f64,
+    // names of the operations from the node backward was called on (e.g. the loss) down to this one
+    pub path: Vec<String>,
+}
+
+impl Display for Anomaly {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.pass {
+            Pass::Forward => write!(f, "{} result of {} on inputs {:?}", self.value, self.op, self.inputs)?,
+            Pass::Backward => write!(f, "{} gradient from {} on inputs {:?}", self.value, self.op, self.inputs)?,
+        }
+        if !self.path.is_empty() {
+            write!(f, ", path from the loss: {}", self.path.join(" -> "))?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Display for OxiError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            OxiError::MissingBackward { op } =>
+                write!(f, "no backward closure to call for this node (operation {:?})", op),
+            OxiError::GraphFreed { op } =>
+                write!(f, "the graph was already freed by a backward pass (operation {:?}), retain it to backpropagate again", op),
+            OxiError::Anomaly(a) => write!(f, "{}", a),
+            OxiError::NotReplayable { op } =>
+                write!(f, "cannot recompute the data of this node (operation {:?})", op),
+            OxiError::NotTwiceDifferentiable { op } =>
+                write!(f, "the custom operation {} can only be differentiated once, without create_graph", op),
+            OxiError::CustomGradients { op, expected, found } =>
+                write!(f, "the custom operation {} returned {} gradients for {} inputs", op, found, expected),
+            OxiError::Borrow => write!(f, "node already borrowed while traversing the graph"),
+            OxiError::EmptyArchitecture => write!(f, "the architecture needs at least one layer"),
+            OxiError::EmptyLayer { layer } => write!(f, "layer {} has no neurons", layer),
+            OxiError::ActivationCount { layers, activations } =>
+                write!(f, "expected {} activations (one per layer), found {}", layers, activations),
+            OxiError::ZeroInputs => write!(f, "the model needs at least one input"),
+            OxiError::DimensionMismatch { expected, found } =>
+                write!(f, "expected {} inputs, found {}", expected, found),
+            OxiError::LengthMismatch { data, labels } =>
+                write!(f, "{} samples but {} labels", data, labels),
+            OxiError::RowWidth { row, expected, found } =>
+                write!(f, "row {} has {} features, expected {}", row, found, expected),
+            OxiError::InvalidK { k, samples } =>
+                write!(f, "cannot split {} samples into {} groups", samples, k),
+            OxiError::InvalidRatios { validation, test } =>
+                write!(f, "validation ({}) and test ({}) ratios must be non-negative and sum up to 1.0 at most", validation, test),
+            OxiError::ClassCount { expected, found } =>
+                write!(f, "expected {} classes, found {}", expected, found),
+            OxiError::Aborted(e) => write!(f, "training aborted: {}", e),
+            OxiError::Csv(e) => write!(f, "{}", e),
+            OxiError::Io(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for OxiError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            OxiError::Csv(e) => Some(e),
+            OxiError::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<CsvError> for OxiError {
+    fn from(e: CsvError) -> Self {
+        OxiError::Csv(e)
+    }
+}
+
+impl From<io::Error> for OxiError {
+    fn from(e: io::Error) -> Self {
+        OxiError::Io(e)
+    }
+}
@@ -0,0 +1,99 @@
+use super::engine::{grad, Value};
+
+// JACOBIAN AND HESSIAN
+// f maps the input leaves to its outputs, the leaves are built from x and discarded afterwards
+
+// dense Jacobian of f at x (a row per output, a column per input) in reverse mode,
+// i.e. a graph built once and a backward pass per output
+pub fn jacobian<F>(f: F, x: &[f64]) -> Vec<Vec<f64>>
+    where F: Fn(&[Value]) -> Vec<Value>
+{
+    let inputs = leaves(x);
+    let outputs = f(&inputs);
+
+    rows(&outputs, &inputs)
+}
+
+// dense Hessian of a scalar f at x, i.e. the Jacobian of its gradient (built with grad as a graph)
+pub fn hessian<F>(f: F, x: &[f64]) -> Vec<Vec<f64>>
+    where F: Fn(&[Value]) -> Value
+{
+    let inputs = leaves(x);
+    let gradient = grad(&f(&inputs), &inputs, true);
+
+    rows(&gradient, &inputs)
+}
+
+fn leaves(x: &[f64]) -> Vec<Value> {
+    x.iter().map(|x| Value::new(*x)).collect()
+}
+
+// backward accumulates into every node it goes through, so the intermediate nodes of each output are reset
+// before its pass, together with the inputs it may not depend on (which would keep the previous row)
+// other leaves (e.g. the parameters of a model captured by f) are left untouched, apart from their
+// grads accumulating as with any backward pass
+fn rows(outputs: &[Value], inputs: &[Value]) -> Vec<Vec<f64>> {
+    outputs.iter()
+        .map(|o| {
+            o.topological_order()
+                .expect("the graph shouldn't be borrowed between passes")
+                .iter()
+                .chain(inputs)
+                .for_each(|n| n.set_grad(0.0));
+            o.backward();
+            inputs.iter().map(|i| i.get_grad()).collect()
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_jacobian() {
+        // outputs sharing nodes and not depending on every input
+        let j = jacobian(
+            |v| {
+                let xy = &v[0] * &v[1];
+                vec![xy.clone(), &xy + &v[2].power(2.0), v[0].clone()]
+            },
+            &[2.0, 3.0, 4.0],
+        );
+
+        assert_eq!(j, vec![
+            vec![3.0, 2.0, 0.0],
+            vec![3.0, 2.0, 8.0],
+            vec![1.0, 0.0, 0.0],
+        ]);
+    }
+
+    #[test]
+    fn test_captured_leaves() {
+        // a parameter used by f keeps the grad it had, only accumulating the passes' ones
+        let w = Value::new(3.0);
+        w.set_grad(10.0);
+        let j = jacobian(|v| vec![&v[0] * &w, &v[0] + &v[1]], &[2.0, 5.0]);
+
+        assert_eq!(j, vec![vec![3.0, 0.0], vec![1.0, 1.0]]);
+        assert_eq!(w.get_grad(), 12.0);
+    }
+
+    #[test]
+    fn test_hessian() {
+        // f = x^2 y + y^3 / z
+        let h = hessian(
+            |v| &(&v[0].power(2.0) * &v[1]) + &(&v[1].power(3.0) / &v[2]),
+            &[1.0, 2.0, 4.0],
+        );
+        let h: Vec<Vec<f64>> = h.iter()
+            .map(|r| r.iter().map(|v| (v * 1_000_f64).round() / 1_000_f64).collect())
+            .collect();
+
+        assert_eq!(h, vec![
+            vec![4.0, 2.0, 0.0],
+            vec![2.0, 3.0, -0.75],
+            vec![0.0, -0.75, 0.25],
+        ]);
+    }
+}
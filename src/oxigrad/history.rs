@@ -0,0 +1,280 @@
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use serde::{Serialize, Deserialize};
+use super::engine::Value;
+
+// TRAINING RECORD IMPLEMENTATION
+// state of a single training pass, with any additional metric (e.g. validation ones) in metrics
+// (NaNs and infinities, e.g. of a diverging training, are written as the strings "NaN", "inf" and "-inf")
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Record {
+    pub pass: usize,
+    #[serde(with = "float")]
+    pub alpha: f64,
+    #[serde(with = "float")]
+    pub pred_mean: f64,
+    #[serde(with = "float")]
+    pub pred_std: f64,
+    #[serde(with = "float")]
+    pub pred_min: f64,
+    #[serde(with = "float")]
+    pub pred_max: f64,
+    #[serde(with = "float")]
+    pub reg: f64,
+    #[serde(with = "float")]
+    pub loss: f64,
+    #[serde(with = "float")]
+    pub tot_loss: f64,
+    #[serde(flatten, with = "float_map")]
+    pub metrics: BTreeMap<String, f64>,
+}
+
+// JSON has no representation for non finite numbers (serde_json would write them as null)
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+enum Float {
+    Number(f64),
+    Text(String),
+}
+
+impl Float {
+    fn new(v: f64) -> Self {
+        if v.is_finite() {
+            Float::Number(v)
+        } else {
+            Float::Text(v.to_string())
+        }
+    }
+
+    fn value<E: serde::de::Error>(self) -> Result<f64, E> {
+        match self {
+            Float::Number(v) => Ok(v),
+            Float::Text(t) => match t.as_str() {
+                "NaN" => Ok(f64::NAN),
+                "inf" => Ok(f64::INFINITY),
+                "-inf" => Ok(f64::NEG_INFINITY),
+                _ => Err(E::custom(format!("invalid number '{}'", t))),
+            },
+        }
+    }
+}
+
+mod float {
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+    use super::Float;
+
+    pub fn serialize<S: Serializer>(v: &f64, s: S) -> Result<S::Ok, S::Error> {
+        Float::new(*v).serialize(s)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<f64, D::Error> {
+        Float::deserialize(d)?.value()
+    }
+}
+
+mod float_map {
+    use std::collections::BTreeMap;
+    use serde::{Deserialize, Deserializer, Serializer};
+    use super::Float;
+
+    pub fn serialize<S: Serializer>(m: &BTreeMap<String, f64>, s: S) -> Result<S::Ok, S::Error> {
+        s.collect_map(m.iter().map(|(k, v)| (k, Float::new(*v))))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<BTreeMap<String, f64>, D::Error> {
+        BTreeMap::<String, Float>::deserialize(d)?
+            .into_iter()
+            .map(|(k, v)| v.value().map(|v| (k, v)))
+            .collect()
+    }
+}
+
+impl Record {
+    pub fn new(pass: usize, alpha: f64, preds: &[Value], reg: &Value, loss: &Value, tot_loss: &Value) -> Self {
+        let data: Vec<f64> = preds.iter().map(|p| p.get_data()).collect();
+        let n = data.len().max(1) as f64;
+        let mean = data.iter().sum::<f64>() / n;
+
+        Record {
+            pass,
+            alpha,
+            pred_mean: mean,
+            pred_std: (data.iter().map(|d| (d - mean).powi(2)).sum::<f64>() / n).sqrt(),
+            pred_min: data.iter().cloned().fold(f64::INFINITY, f64::min),
+            pred_max: data.iter().cloned().fold(f64::NEG_INFINITY, f64::max),
+            reg: reg.get_data(),
+            loss: loss.get_data(),
+            tot_loss: tot_loss.get_data(),
+            metrics: BTreeMap::new(),
+        }
+    }
+
+    pub fn metric(mut self, name: &str, value: f64) -> Self {
+        self.metrics.insert(name.to_string(), value);
+        self
+    }
+
+    // value of a column (any of the fixed fields or of the additional metrics), if recorded
+    pub fn get(&self, name: &str) -> Option<f64> {
+        match name {
+            "pass" => Some(self.pass as f64),
+            "alpha" => Some(self.alpha),
+            "pred_mean" => Some(self.pred_mean),
+            "pred_std" => Some(self.pred_std),
+            "pred_min" => Some(self.pred_min),
+            "pred_max" => Some(self.pred_max),
+            "reg" => Some(self.reg),
+            "loss" => Some(self.loss),
+            "tot_loss" => Some(self.tot_loss),
+            _ => self.metrics.get(name).cloned(),
+        }
+    }
+}
+
+// TRAINING HISTORY IMPLEMENTATION
+// records of all the passes of a training, exportable to CSV and JSON Lines for external tools
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct History {
+    pub records: Vec<Record>,
+}
+
+impl History {
+    pub fn new() -> Self {
+        History::default()
+    }
+
+    pub fn push(&mut self, record: Record) {
+        self.records.push(record);
+    }
+
+    pub fn len(&self) -> usize {
+        self.records.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.records.is_empty()
+    }
+
+    // values of a column along the whole training (any of the fixed fields or of the additional metrics)
+    pub fn series(&self, name: &str) -> Vec<f64> {
+        self.records.iter()
+            .filter_map(|r| r.get(name))
+            .collect()
+    }
+
+    // additional metrics' names as they appear in the records (sorted, without duplicates)
+    fn metric_names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.records.iter()
+            .flat_map(|r| r.metrics.keys().cloned())
+            .collect();
+        names.sort();
+        names.dedup();
+        names
+    }
+
+    pub fn write_csv<W: Write>(&self, mut w: W) -> io::Result<()> {
+        let metrics = self.metric_names();
+
+        let mut header = vec!["pass", "alpha", "pred_mean", "pred_std", "pred_min", "pred_max", "reg", "loss", "tot_loss"];
+        header.extend(metrics.iter().map(|m| m.as_str()));
+        writeln!(w, "{}", header.join(","))?;
+
+        for r in self.records.iter() {
+            let mut row = vec![r.pass.to_string()];
+            row.extend(
+                [r.alpha, r.pred_mean, r.pred_std, r.pred_min, r.pred_max, r.reg, r.loss, r.tot_loss]
+                    .iter()
+                    .map(|v| v.to_string())
+            );
+            // metrics missing from a record are left empty
+            row.extend(metrics.iter().map(|m| r.metrics.get(m).map_or(String::new(), |v| v.to_string())));
+            writeln!(w, "{}", row.join(","))?;
+        }
+
+        Ok(())
+    }
+
+    pub fn write_jsonl<W: Write>(&self, mut w: W) -> io::Result<()> {
+        for r in self.records.iter() {
+            serde_json::to_writer(&mut w, r)?;
+            writeln!(w)?;
+        }
+
+        Ok(())
+    }
+
+    pub fn to_csv<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let mut w = BufWriter::new(File::create(path)?);
+        self.write_csv(&mut w)?;
+        w.flush()
+    }
+
+    pub fn to_jsonl<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let mut w = BufWriter::new(File::create(path)?);
+        self.write_jsonl(&mut w)?;
+        w.flush()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn history() -> History {
+        let preds = [Value::new(-1.0), Value::new(1.0), Value::new(3.0)];
+        let mut h = History::new();
+        h.push(Record::new(0, 0.03, &preds, &Value::new(0.5), &Value::new(2.0), &Value::new(2.5)).metric("val_loss", 1.5));
+        h.push(Record::new(1, 0.02, &preds[..1], &Value::new(0.25), &Value::new(1.0), &Value::new(1.25)));
+        h
+    }
+
+    #[test]
+    fn test_record() {
+        let r = &history().records[0];
+
+        assert_eq!(r.pred_mean, 1.0);
+        assert_eq!(r.pred_min, -1.0);
+        assert_eq!(r.pred_max, 3.0);
+        assert_eq!((r.pred_std * 1_000_f64).round() / 1_000_f64, 1.633);
+        assert_eq!(history().series("val_loss"), vec![1.5]);
+        assert_eq!(history().series("tot_loss"), vec![2.5, 1.25]);
+    }
+
+    #[test]
+    fn test_csv() {
+        let mut out = Vec::new();
+        history().write_csv(&mut out).unwrap();
+        let csv = String::from_utf8(out).unwrap();
+        let lines: Vec<&str> = csv.lines().collect();
+
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[0], "pass,alpha,pred_mean,pred_std,pred_min,pred_max,reg,loss,tot_loss,val_loss");
+        assert!(lines[1].starts_with("0,0.03,1,"));
+        assert!(lines[1].ends_with(",0.5,2,2.5,1.5"));
+        assert_eq!(lines[2], "1,0.02,-1,0,-1,-1,0.25,1,1.25,");
+    }
+
+    #[test]
+    fn test_jsonl() {
+        let mut out = Vec::new();
+        history().write_jsonl(&mut out).unwrap();
+        let jsonl = String::from_utf8(out).unwrap();
+
+        let records: Vec<Record> = jsonl.lines().map(|l| serde_json::from_str(l).unwrap()).collect();
+        assert_eq!(records, history().records);
+        assert!(jsonl.lines().next().unwrap().contains("\"val_loss\":1.5"));
+
+        // a diverging training can be read back
+        let preds = [Value::new(f64::NAN)];
+        let r = Record::new(2, 0.01, &preds, &Value::new(f64::INFINITY), &Value::new(f64::NEG_INFINITY), &Value::new(f64::NAN))
+            .metric("val_loss", f64::INFINITY);
+        let line = serde_json::to_string(&r).unwrap();
+        assert!(line.contains("\"reg\":\"inf\"") && line.contains("\"loss\":\"-inf\"") && line.contains("\"val_loss\":\"inf\""));
+        let back: Record = serde_json::from_str(&line).unwrap();
+        assert!(back.pred_mean.is_nan() && back.tot_loss.is_nan());
+        assert_eq!((back.reg, back.loss, back.get("val_loss")), (f64::INFINITY, f64::NEG_INFINITY, Some(f64::INFINITY)));
+        assert!(serde_json::from_str::<Record>(&line.replace("\"inf\"", "\"big\"")).is_err());
+    }
+}
@@ -0,0 +1,221 @@
+use std::fmt::Write;
+use super::nn::Model;
+use super::plot::Grid;
+use super::history::History;
+
+// colors for positive and negative predictions/labels
+const POSITIVE: (u8, u8, u8) = (31, 119, 180);
+const NEGATIVE: (u8, u8, u8) = (214, 39, 40);
+// palette used for the series of a line chart
+const PALETTE: [&str; 6] = ["#1f77b4", "#ff7f0e", "#2ca02c", "#d62728", "#9467bd", "#8c564b"];
+// side (in pixels) of the square whose center is evaluated by the model in bitmaps
+const PIXELS_PER_CELL: usize = 4;
+
+// region color, lighter the closer the prediction is to the boundary
+fn region_color(value: f64) -> (u8, u8, u8) {
+    let base = if value > 0.0 { POSITIVE } else { NEGATIVE };
+    let strength = 0.15 + 0.35 * value.abs().min(1.0);
+    let mix = |c: u8| (255.0 - (255.0 - c as f64) * strength).round() as u8;
+    (mix(base.0), mix(base.1), mix(base.2))
+}
+
+fn hex((r, g, b): (u8, u8, u8)) -> String {
+    format!("#{:02x}{:02x}{:02x}", r, g, b)
+}
+
+// DECISION BOUNDARY (SVG)
+// decision regions of a 2 inputs model evaluated on a cols x rows grid, with the data points overlaid
+pub fn decision_boundary_svg(model: &Model, data: &[Vec<f64>], labels: &[f64], width: usize, height: usize, cols: usize, rows: usize) -> String {
+    let grid = Grid::evaluate(model, data, cols, rows);
+    let (cw, ch) = (width as f64 / grid.cols as f64, height as f64 / grid.rows as f64);
+    let mut svg = String::new();
+
+    let _ = writeln!(svg, r#"<svg xmlns="http://www.w3.org/2000/svg" width="{}" height="{}" viewBox="0 0 {} {}">"#, width, height, width, height);
+    let _ = writeln!(svg, r#"<g shape-rendering="crispEdges">"#);
+    for (r, line) in grid.values.iter().enumerate() {
+        for (c, v) in line.iter().enumerate() {
+            let _ = writeln!(
+                svg,
+                r#"<rect x="{:.2}" y="{:.2}" width="{:.2}" height="{:.2}" fill="{}"/>"#,
+                c as f64 * cw, r as f64 * ch, cw, ch, hex(region_color(*v)),
+            );
+        }
+    }
+    let _ = writeln!(svg, "</g>");
+
+    let (x0, x1) = grid.x_range;
+    let (y0, y1) = grid.y_range;
+    for (point, label) in data.iter().zip(labels.iter()) {
+        let _ = writeln!(
+            svg,
+            r##"<circle cx="{:.2}" cy="{:.2}" r="4" fill="{}" stroke="#000000" stroke-width="1"/>"##,
+            (point[0] - x0) / (x1 - x0) * width as f64,
+            (y1 - point[1]) / (y1 - y0) * height as f64,
+            hex(if *label > 0.0 { POSITIVE } else { NEGATIVE }),
+        );
+    }
+    svg.push_str("</svg>\n");
+
+    svg
+}
+
+// DECISION BOUNDARY (PPM)
+// same as decision_boundary_svg but as a binary PPM (P6) bitmap, which needs no dependency to be written
+pub fn decision_boundary_ppm(model: &Model, data: &[Vec<f64>], labels: &[f64], width: usize, height: usize) -> Vec<u8> {
+    let grid = Grid::evaluate(model, data, width.div_ceil(PIXELS_PER_CELL), height.div_ceil(PIXELS_PER_CELL));
+    let mut pixels: Vec<(u8, u8, u8)> = (0..width * height)
+        .map(|i| region_color(grid.values[(i / width) / PIXELS_PER_CELL][(i % width) / PIXELS_PER_CELL]))
+        .collect();
+
+    // data points as black bordered squares
+    let (x0, x1) = grid.x_range;
+    let (y0, y1) = grid.y_range;
+    for (point, label) in data.iter().zip(labels.iter()) {
+        let px = ((point[0] - x0) / (x1 - x0) * width as f64) as i64;
+        let py = ((y1 - point[1]) / (y1 - y0) * height as f64) as i64;
+        for dy in -3..=3_i64 {
+            for dx in -3..=3_i64 {
+                let (x, y) = (px + dx, py + dy);
+                if x < 0 || y < 0 || x >= width as i64 || y >= height as i64 {
+                    continue;
+                }
+                let border = dx.abs() == 3 || dy.abs() == 3;
+                pixels[y as usize * width + x as usize] = if border {
+                    (0, 0, 0)
+                } else if *label > 0.0 {
+                    POSITIVE
+                } else {
+                    NEGATIVE
+                };
+            }
+        }
+    }
+
+    let mut ppm = format!("P6\n{} {}\n255\n", width, height).into_bytes();
+    for (r, g, b) in pixels {
+        ppm.extend_from_slice(&[r, g, b]);
+    }
+
+    ppm
+}
+
+// LEARNING CURVES (SVG)
+// line chart of some of the series of a training history (e.g. "loss" and "val_loss") along the passes
+pub fn history_svg(history: &History, series: &[&str], width: usize, height: usize) -> String {
+    let (left, right, top, bottom) = (60.0, 20.0, 20.0, 40.0);
+    let (w, h) = (width as f64 - left - right, height as f64 - top - bottom);
+
+    let lines: Vec<(&str, Vec<(f64, f64)>)> = series.iter()
+        .map(|s| {
+            let points = history.records.iter()
+                .filter_map(|r| r.get(s).filter(|v| v.is_finite()).map(|v| (r.pass as f64, v)))
+                .collect();
+            (*s, points)
+        })
+        .collect();
+
+    let all = || lines.iter().flat_map(|(_, pts)| pts.iter());
+    let (xmin, xmax) = all().fold((f64::INFINITY, f64::NEG_INFINITY), |(lo, hi), (x, _)| (lo.min(*x), hi.max(*x)));
+    let (ymin, ymax) = all().fold((f64::INFINITY, f64::NEG_INFINITY), |(lo, hi), (_, y)| (lo.min(*y), hi.max(*y)));
+    let xspan = if xmax > xmin { xmax - xmin } else { 1.0 };
+    let yspan = if ymax > ymin { ymax - ymin } else { 1.0 };
+    let sx = |x: f64| left + (x - xmin) / xspan * w;
+    let sy = |y: f64| top + (ymax - y) / yspan * h;
+
+    let mut svg = String::new();
+    let _ = writeln!(svg, r#"<svg xmlns="http://www.w3.org/2000/svg" width="{}" height="{}" viewBox="0 0 {} {}" font-family="sans-serif" font-size="12">"#, width, height, width, height);
+    let _ = writeln!(svg, r##"<rect width="{}" height="{}" fill="#ffffff"/>"##, width, height);
+
+    // axes with the extremes of both ranges
+    if all().next().is_some() {
+        let _ = writeln!(svg, r##"<path d="M{:.2} {:.2} V{:.2} H{:.2}" fill="none" stroke="#000000"/>"##, left, top, top + h, left + w);
+        let _ = writeln!(svg, r#"<text x="{:.2}" y="{:.2}" text-anchor="end">{:.4}</text>"#, left - 5.0, top + 4.0, ymax);
+        let _ = writeln!(svg, r#"<text x="{:.2}" y="{:.2}" text-anchor="end">{:.4}</text>"#, left - 5.0, top + h + 4.0, ymin);
+        let _ = writeln!(svg, r#"<text x="{:.2}" y="{:.2}" text-anchor="middle">{}</text>"#, left, top + h + 18.0, xmin);
+        let _ = writeln!(svg, r#"<text x="{:.2}" y="{:.2}" text-anchor="middle">{}</text>"#, left + w, top + h + 18.0, xmax);
+        let _ = writeln!(svg, r#"<text x="{:.2}" y="{:.2}" text-anchor="middle">pass</text>"#, left + w / 2.0, top + h + 32.0);
+    }
+
+    for (i, (name, points)) in lines.iter().enumerate() {
+        let color = PALETTE[i % PALETTE.len()];
+        // passes whose value wasn't finite are left as gaps in the line
+        let path: Vec<String> = points.iter()
+            .enumerate()
+            .map(|(j, (x, y))| {
+                let gap = j == 0 || *x > points[j - 1].0 + 1.0;
+                format!("{}{:.2} {:.2}", if gap { "M" } else { "L" }, sx(*x), sy(*y))
+            })
+            .collect();
+        if !path.is_empty() {
+            let _ = writeln!(svg, r#"<path d="{}" fill="none" stroke="{}" stroke-width="2"/>"#, path.join(" "), color);
+        }
+
+        // legend on the top right corner
+        let ly = top + 10.0 + 16.0 * i as f64;
+        let _ = writeln!(svg, r#"<line x1="{:.2}" y1="{:.2}" x2="{:.2}" y2="{:.2}" stroke="{}" stroke-width="2"/>"#, left + w - 110.0, ly, left + w - 90.0, ly, color);
+        let _ = writeln!(svg, r#"<text x="{:.2}" y="{:.2}">{}</text>"#, left + w - 85.0, ly + 4.0, name);
+    }
+    svg.push_str("</svg>\n");
+
+    svg
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use super::super::plot::identity_x;
+    use super::super::history::Record;
+    use super::super::engine::Value;
+
+    #[test]
+    fn test_decision_boundary_svg() {
+        let data = vec![vec![-1.0, 0.0], vec![1.0, 0.0]];
+        let svg = decision_boundary_svg(&identity_x(), &data, &[-1.0, 1.0], 200, 100, 10, 5);
+
+        assert!(svg.starts_with("<svg"));
+        assert!(svg.trim_end().ends_with("</svg>"));
+        assert_eq!(svg.matches("<rect").count(), 50);
+        assert_eq!(svg.matches("<circle").count(), 2);
+        assert!(svg.contains(&format!(r#"fill="{}" stroke"#, hex(POSITIVE))));
+    }
+
+    #[test]
+    fn test_decision_boundary_ppm() {
+        let data = vec![vec![-1.0, 0.0], vec![1.0, 0.0]];
+        let ppm = decision_boundary_ppm(&identity_x(), &data, &[-1.0, 1.0], 40, 20);
+        let header = b"P6\n40 20\n255\n";
+
+        assert_eq!(&ppm[..header.len()], header);
+        assert_eq!(ppm.len(), header.len() + 40 * 20 * 3);
+
+        // top left pixel is in the negative region, top right one in the positive one
+        let pixel = |x: usize, y: usize| {
+            let i = header.len() + (y * 40 + x) * 3;
+            (ppm[i], ppm[i + 1], ppm[i + 2])
+        };
+        assert_eq!(pixel(0, 0), region_color(-1.0));
+        assert_eq!(pixel(39, 0), region_color(1.0));
+    }
+
+    #[test]
+    fn test_history_svg() {
+        let mut h = History::new();
+        for pass in 0..5 {
+            let loss = Value::new(1.0 / (pass + 1) as f64);
+            h.push(Record::new(pass, 0.01, &[], &Value::new(0.0), &loss, &loss).metric("val_loss", 0.5));
+        }
+        let svg = history_svg(&h, &["loss", "val_loss"], 400, 300);
+
+        assert_eq!(svg.matches("stroke-width=\"2\"/>").count(), 4);
+        assert!(svg.contains(">loss</text>"));
+        assert!(svg.contains(">val_loss</text>"));
+        assert!(svg.contains(">1.0000</text>"));
+
+        // a diverged pass breaks the line without moving the others
+        h.records[2].loss = f64::NAN;
+        let svg = history_svg(&h, &["loss"], 400, 300);
+        let path = svg.lines().find(|l| l.starts_with("<path d=\"M") && l.contains("stroke-width")).unwrap();
+        assert_eq!(path.matches('M').count(), 2);
+        assert_eq!(path.matches('L').count(), 2);
+    }
+}
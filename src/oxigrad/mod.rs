@@ -2,4 +2,20 @@ pub mod nn;
 pub mod engine;
 pub mod utils;
 pub mod xval;
-pub mod data;
\ No newline at end of file
+pub mod data;
+pub mod sync_engine;
+pub mod dataset;
+pub mod preprocessing;
+pub mod persist;
+pub mod callbacks;
+pub mod history;
+pub mod plot;
+pub mod image;
+pub mod optim;
+pub mod train;
+pub mod config;
+pub mod error;
+pub mod dual;
+pub mod functional;
+pub mod tape;
+pub mod compile;
\ No newline at end of file
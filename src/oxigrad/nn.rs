@@ -1,26 +1,31 @@
-use crate::oxigrad::engine::Value;
+use std::ops;
+use crate::oxigrad::engine::{Backend, Value};
+use crate::oxigrad::sync_engine::SyncValue;
+use crate::oxigrad::tape::TapeValue;
+use crate::oxigrad::error::OxiError;
 use rand::{thread_rng, Rng};
+use serde::{Serialize, Deserialize};
 
 // BASE TRAIT
-pub trait Base {
+pub trait Base<V: Backend = Value> {
     fn zero_grad(&self) {
-        for p in self.params().iter_mut() {
-            p.core.borrow().grad.set(0.0);
+        for p in self.params().iter() {
+            p.set_grad(0.0);
         }
     }
 
-    fn params(&self) -> Vec<Value>;
+    fn params(&self) -> Vec<V>;
 }
 
 // NEURON IMPLEMENTATION
-struct Neuron {
-    weights: Vec<Value>,
-    bias: Value,
+struct Neuron<V: Backend = Value> {
+    weights: Vec<V>,
+    bias: V,
     nonlin: bool,
 }
 
-impl Base for Neuron {
-    fn params(&self) -> Vec<Value> {
+impl<V: Backend> Base<V> for Neuron<V> {
+    fn params(&self) -> Vec<V> {
         let mut ps = vec![];
 
         for w in self.weights.clone() {
@@ -32,26 +37,28 @@ impl Base for Neuron {
     }
 }
 
-impl Neuron {
+impl<V: Backend> Neuron<V>
+    where for<'a> &'a V: ops::Add<&'a V, Output = V> + ops::Mul<&'a V, Output = V>
+{
     fn new(num_weights: usize, nonlin: bool) -> Self {
         Neuron {
             weights: (0..num_weights)
                 .map(|_| thread_rng().gen_range::<f64>(-1.0, 1.0))
-                .map(|v| Value::new(v))
+                .map(V::from_data)
                 .collect(),
-            bias: Value::new(0.0),
+            bias: V::from_data(0.0),
             nonlin,
         }
     }
 
-    fn forward(&self, inputs: &Vec<Value>) -> Value {
+    fn forward(&self, inputs: &[V]) -> V {
         let mut dot = inputs.iter()
             .zip(self.weights.iter())
             .fold(
-                Value::new(0.0),
-                |mut s, (x, w)| { s = s + &(x * w); s },
+                V::constant(0.0),
+                |mut s, (x, w)| { s = &s + &(x * w); s },
             );
-        dot = dot + &self.bias;
+        dot = &dot + &self.bias;
 
         if self.nonlin {
             dot.relu()
@@ -62,12 +69,12 @@ impl Neuron {
 }
 
 // LAYER IMPLEMENTATION
-struct Layer {
-    neurons: Vec<Neuron>,
+struct Layer<V: Backend = Value> {
+    neurons: Vec<Neuron<V>>,
 }
 
-impl Base for Layer {
-    fn params(&self) -> Vec<Value> {
+impl<V: Backend> Base<V> for Layer<V> {
+    fn params(&self) -> Vec<V> {
         let mut ps = vec![];
 
         for n in self.neurons.iter() {
@@ -80,10 +87,12 @@ impl Base for Layer {
     }
 }
 
-impl Layer {
+impl<V: Backend> Layer<V>
+    where for<'a> &'a V: ops::Add<&'a V, Output = V> + ops::Mul<&'a V, Output = V>
+{
     fn new(num_weights: usize, neurons: usize, nonlin: bool) -> Self {
         let mut l = Layer {
-            neurons: Vec::<Neuron>::new(),
+            neurons: Vec::<Neuron<V>>::new(),
         };
 
         for _n in 0..neurons {
@@ -93,18 +102,42 @@ impl Layer {
         l
     }
 
-    fn forward(&self, inputs: Vec<Value>) -> Vec<Value> {
+    fn forward(&self, inputs: Vec<V>) -> Vec<V> {
         self.neurons.iter().map(|n| n.forward(&inputs)).collect()
     }
 }
 
+// activation applied to the outputs of a layer's neurons
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Activation {
+    ReLU,
+    Linear,
+}
+
+impl Activation {
+    // ReLU on every hidden layer and a linear output, as used by Model::new
+    pub fn defaults(layers: usize) -> Vec<Activation> {
+        (0..layers)
+            .map(|l| if l + 1 == layers { Activation::Linear } else { Activation::ReLU })
+            .collect()
+    }
+}
+
 // MODEL IMPLEMENTATION
-pub struct Model {
-    layers: Vec<Layer>,
+pub struct Model<V: Backend = Value> {
+    layers: Vec<Layer<V>>,
 }
 
-impl Base for Model {
-    fn params(&self) -> Vec<Value> {
+// model whose parameters can be shared with or moved between threads
+// (e.g. trained in one thread and served for inference from others)
+pub type SyncModel = Model<SyncValue>;
+
+// model whose graph is recorded on this thread's tape (see tape::scope to release each pass)
+pub type TapeModel = Model<TapeValue>;
+
+impl<V: Backend> Base<V> for Model<V> {
+    fn params(&self) -> Vec<V> {
         let mut ps = vec![];
 
         for l in self.layers.iter() {
@@ -118,7 +151,62 @@ impl Base for Model {
 }
 
 impl Model {
-    pub fn new(input_size: usize, arch: &Vec<usize>) -> Self {
+    pub fn new(input_size: usize, arch: &[usize]) -> Self {
+        Model::with_backend(input_size, arch)
+    }
+
+    // same as Model::new but returning an error for architectures that can't be built
+    pub fn try_new(input_size: usize, arch: &[usize]) -> Result<Self, OxiError> {
+        Model::try_with_backend(input_size, arch)
+    }
+
+    // same as Model::new but with the activation of each layer given explicitly
+    pub fn with_activations(input_size: usize, arch: &[usize], activations: &[Activation]) -> Self {
+        Model::build(input_size, arch, activations)
+    }
+
+    // same as Model::with_activations but returning an error for architectures that can't be built
+    pub fn try_with_activations(input_size: usize, arch: &[usize], activations: &[Activation]) -> Result<Self, OxiError> {
+        Model::<Value>::check(input_size, arch)?;
+        if activations.len() != arch.len() {
+            return Err(OxiError::ActivationCount { layers: arch.len(), activations: activations.len() });
+        }
+
+        Ok(Model::build(input_size, arch, activations))
+    }
+}
+
+impl<V: Backend> Model<V>
+    where for<'a> &'a V: ops::Add<&'a V, Output = V> + ops::Mul<&'a V, Output = V>
+{
+    // same as Model::new but for any node type (e.g. SyncModel::with_backend)
+    pub fn with_backend(input_size: usize, arch: &[usize]) -> Self {
+        Model::build(input_size, arch, &Activation::defaults(arch.len()))
+    }
+
+    pub fn try_with_backend(input_size: usize, arch: &[usize]) -> Result<Self, OxiError> {
+        Model::check(input_size, arch)?;
+
+        Ok(Model::with_backend(input_size, arch))
+    }
+
+    fn check(input_size: usize, arch: &[usize]) -> Result<(), OxiError> {
+        if arch.is_empty() {
+            return Err(OxiError::EmptyArchitecture);
+        }
+        if input_size == 0 {
+            return Err(OxiError::ZeroInputs);
+        }
+        if let Some(layer) = arch.iter().position(|n| *n == 0) {
+            return Err(OxiError::EmptyLayer { layer });
+        }
+
+        Ok(())
+    }
+
+    fn build(input_size: usize, arch: &[usize], activations: &[Activation]) -> Self {
+        assert_eq!(arch.len(), activations.len(), "Every layer needs exactly one activation");
+
         // initialize NN architecture
         let mut nn_arch = Vec::new();
         nn_arch.push(input_size);
@@ -126,7 +214,7 @@ impl Model {
 
         // initialize model
         let mut m = Model {
-            layers: Vec::<Layer>::new(),
+            layers: Vec::<Layer<V>>::new(),
         };
 
         // initialize model's layers
@@ -134,30 +222,71 @@ impl Model {
             m.layers.push(Layer::new(
                 nn_arch[l],
                 nn_arch[l+1],
-                l!=arch.len()-1,
+                activations[l] == Activation::ReLU,
             ))
         }
 
         m
     }
 
-    // pub fn forward(&self, inputs: &[f64; 2]) -> Vec<Value> {
-    pub fn forward(&self, inputs: &[f64; 2]) -> Value {
-        // multiply inputs for each layers and collect results
-        let mut is: Vec<Value> = inputs
+    // pub fn forward(&self, inputs: &[f64]) -> Vec<Value> {
+    // panics where try_forward returns an error (extra inputs would be ignored, missing ones treated as 0)
+    pub fn forward(&self, inputs: &[f64]) -> V {
+        assert_eq!(inputs.len(), self.input_size(), "The model expects {} inputs", self.input_size());
+        let is: Vec<V> = inputs
             .iter()
-            .map(|v| Value::new(*v))
+            .map(|v| V::constant(*v))
             .collect();
 
+        self.forward_nodes(&is)
+    }
+
+    // same as forward but on nodes, e.g. placeholders (see CompiledGraph::trace)
+    pub fn forward_nodes(&self, inputs: &[V]) -> V {
+        // multiply inputs for each layers and collect results
+        let mut is = inputs.to_vec();
+
         for l in &self.layers {
             is = l.forward(is);
         }
 
         is[0].clone()
     }
+
+    // same as forward but with the number of inputs checked against the model's one
+    pub fn try_forward(&self, inputs: &[f64]) -> Result<V, OxiError> {
+        if inputs.len() != self.input_size() {
+            return Err(OxiError::DimensionMismatch { expected: self.input_size(), found: inputs.len() });
+        }
+
+        Ok(self.forward(inputs))
+    }
+
+    // number of inputs the model expects
+    pub fn input_size(&self) -> usize {
+        self.layers.first()
+            .and_then(|l| l.neurons.first())
+            .map_or(0, |n| n.weights.len())
+    }
+
+    // number of neurons of each layer (as given to Model::new)
+    pub fn arch(&self) -> Vec<usize> {
+        self.layers.iter().map(|l| l.neurons.len()).collect()
+    }
+
+    pub fn activations(&self) -> Vec<Activation> {
+        self.layers.iter()
+            .map(|l| match l.neurons.first() {
+                Some(n) if n.nonlin => Activation::ReLU,
+                _ => Activation::Linear,
+            })
+            .collect()
+    }
 }
 
 #[cfg(test)]
+// the tests predating the lints are kept as written
+#[allow(clippy::useless_vec)]
 mod test {
     use super::*;
 
@@ -170,7 +299,7 @@ mod test {
 
     #[test]
     fn test_neuron() {
-        let n = Neuron::new(10, true);
+        let n: Neuron = Neuron::new(10, true);
 
         assert!(n.weights.len() == 10);
         assert!(n.bias.core.borrow().data.get() == 0.0);
@@ -181,7 +310,7 @@ mod test {
 
     #[test]
     fn test_layer() {
-        let l = Layer::new(8, 2, false);
+        let l: Layer = Layer::new(8, 2, false);
 
         assert!(l.neurons.len() == 2);
         assert!(l.neurons.first().unwrap().bias.core.borrow().data.get() == 0.0);
@@ -200,4 +329,77 @@ mod test {
         m.zero_grad();
         assert!(grad_sum(m.params()) == 0.0);
     }
+
+    #[test]
+    fn test_model_arch() {
+        let m = Model::new(3, &[5, 4, 1]);
+
+        assert_eq!(m.input_size(), 3);
+        assert_eq!(m.arch(), vec![5, 4, 1]);
+        assert_eq!(m.params().len(), (3 + 1) * 5 + (5 + 1) * 4 + (4 + 1));
+        assert_eq!(m.activations(), vec![Activation::ReLU, Activation::ReLU, Activation::Linear]);
+    }
+
+    #[test]
+    fn test_try_new() {
+        assert!(matches!(Model::try_new(2, &[]), Err(OxiError::EmptyArchitecture)));
+        assert!(matches!(Model::try_new(0, &[4, 1]), Err(OxiError::ZeroInputs)));
+        assert!(matches!(Model::try_new(2, &[4, 0, 1]), Err(OxiError::EmptyLayer { layer: 1 })));
+        assert!(matches!(
+            Model::try_with_activations(2, &[4, 1], &[Activation::ReLU]),
+            Err(OxiError::ActivationCount { layers: 2, activations: 1 })
+        ));
+        assert!(matches!(Model::try_with_activations(0, &[1], &[Activation::Linear]), Err(OxiError::ZeroInputs)));
+
+        let m = Model::try_new(2, &[4, 1]).unwrap();
+        assert!(m.try_forward(&[1.0, 2.0]).is_ok());
+        assert!(matches!(m.try_forward(&[1.0]), Err(OxiError::DimensionMismatch { expected: 2, found: 1 })));
+    }
+
+    #[test]
+    fn test_model_activations() {
+        let m = Model::with_activations(1, &[1, 1], &[Activation::Linear, Activation::ReLU]);
+        for p in m.params().iter() {
+            p.set_data(1.0);
+        }
+
+        // (-3 * 1 + 1) * 1 + 1 = -1 goes through the linear hidden layer, then the ReLU output clips it
+        assert_eq!(m.activations(), vec![Activation::Linear, Activation::ReLU]);
+        assert_eq!(m.forward(&[-3.0]).get_data(), 0.0);
+    }
+
+    #[test]
+    fn test_sync_model() {
+        use std::sync::Arc;
+        use std::thread;
+
+        let inputs = [0.5, -0.5];
+
+        // training in a separate thread
+        let m = thread::spawn(move || {
+            let m = SyncModel::with_backend(2, &[4, 1]);
+            for _ in 0..20 {
+                m.zero_grad();
+                let loss = (&m.forward(&inputs) - &SyncValue::new(1.0)).power(2.0);
+                loss.backward();
+                for p in m.params().iter() {
+                    p.set_data(p.get_data() - 0.05 * p.get_grad());
+                }
+            }
+            m
+        }).join().unwrap();
+
+        // inference served concurrently from other threads
+        let m = Arc::new(m);
+        let expected = m.forward(&inputs).get_data();
+        let handles: Vec<_> = (0..4)
+            .map(|_| {
+                let m = Arc::clone(&m);
+                thread::spawn(move || m.forward(&inputs).get_data())
+            })
+            .collect();
+        for h in handles {
+            assert_eq!(h.join().unwrap(), expected);
+        }
+    }
 }
@@ -0,0 +1,201 @@
+use super::engine::Value;
+
+// OPTIMIZER TRAIT
+// updates the parameters of a model using the gradients computed by the last backward pass
+// (parameters must always be given in the same order, e.g. the one of Base::params)
+pub trait Optimizer {
+    fn step(&mut self, params: &[Value]);
+
+    fn lr(&self) -> f64;
+
+    // learning rate changes (e.g. by a schedule) are applied from the next step on
+    fn set_lr(&mut self, lr: f64);
+}
+
+// optimizer by name (as used on the command line), None if the name is unknown
+pub fn by_name(name: &str, lr: f64) -> Option<Box<dyn Optimizer>> {
+    match name {
+        "sgd" => Some(Box::new(Sgd::new(lr))),
+        "momentum" => Some(Box::new(Momentum::new(lr))),
+        "adam" => Some(Box::new(Adam::new(lr))),
+        _ => None,
+    }
+}
+
+pub const NAMES: [&str; 3] = ["sgd", "momentum", "adam"];
+
+// STOCHASTIC GRADIENT DESCENT IMPLEMENTATION
+pub struct Sgd {
+    lr: f64,
+}
+
+impl Sgd {
+    pub fn new(lr: f64) -> Self {
+        Sgd { lr }
+    }
+}
+
+impl Optimizer for Sgd {
+    fn step(&mut self, params: &[Value]) {
+        for p in params.iter() {
+            p.set_data(p.get_data() - self.lr * p.get_grad());
+        }
+    }
+
+    fn lr(&self) -> f64 {
+        self.lr
+    }
+
+    fn set_lr(&mut self, lr: f64) {
+        self.lr = lr;
+    }
+}
+
+// MOMENTUM IMPLEMENTATION
+// gradient descent following an exponentially decaying average of the past gradients
+pub struct Momentum {
+    lr: f64,
+    beta: f64,
+    velocity: Vec<f64>,
+}
+
+impl Momentum {
+    pub fn new(lr: f64) -> Self {
+        Momentum {
+            lr,
+            beta: 0.9,
+            velocity: Vec::new(),
+        }
+    }
+
+    pub fn beta(mut self, beta: f64) -> Self {
+        self.beta = beta;
+        self
+    }
+}
+
+impl Optimizer for Momentum {
+    fn step(&mut self, params: &[Value]) {
+        self.velocity.resize(params.len(), 0.0);
+
+        for (p, v) in params.iter().zip(self.velocity.iter_mut()) {
+            *v = self.beta * *v + p.get_grad();
+            p.set_data(p.get_data() - self.lr * *v);
+        }
+    }
+
+    fn lr(&self) -> f64 {
+        self.lr
+    }
+
+    fn set_lr(&mut self, lr: f64) {
+        self.lr = lr;
+    }
+}
+
+// ADAM IMPLEMENTATION
+// per parameter step sizes from bias corrected estimates of the gradients' first and second moments
+pub struct Adam {
+    lr: f64,
+    beta1: f64,
+    beta2: f64,
+    eps: f64,
+    t: i32,
+    m: Vec<f64>,
+    v: Vec<f64>,
+}
+
+impl Adam {
+    pub fn new(lr: f64) -> Self {
+        Adam {
+            lr,
+            beta1: 0.9,
+            beta2: 0.999,
+            eps: 1e-8,
+            t: 0,
+            m: Vec::new(),
+            v: Vec::new(),
+        }
+    }
+
+    pub fn betas(mut self, beta1: f64, beta2: f64) -> Self {
+        self.beta1 = beta1;
+        self.beta2 = beta2;
+        self
+    }
+
+    pub fn eps(mut self, eps: f64) -> Self {
+        self.eps = eps;
+        self
+    }
+}
+
+impl Optimizer for Adam {
+    fn step(&mut self, params: &[Value]) {
+        self.m.resize(params.len(), 0.0);
+        self.v.resize(params.len(), 0.0);
+        self.t += 1;
+        let (c1, c2) = (1.0 - self.beta1.powi(self.t), 1.0 - self.beta2.powi(self.t));
+
+        for ((p, m), v) in params.iter().zip(self.m.iter_mut()).zip(self.v.iter_mut()) {
+            let g = p.get_grad();
+            *m = self.beta1 * *m + (1.0 - self.beta1) * g;
+            *v = self.beta2 * *v + (1.0 - self.beta2) * g * g;
+            p.set_data(p.get_data() - self.lr * (*m / c1) / ((*v / c2).sqrt() + self.eps));
+        }
+    }
+
+    fn lr(&self) -> f64 {
+        self.lr
+    }
+
+    fn set_lr(&mut self, lr: f64) {
+        self.lr = lr;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    // minimize (x - 3)^2 starting from x = 0
+    fn minimize(opt: &mut dyn Optimizer, steps: usize) -> f64 {
+        let x = Value::new(0.0);
+        for _ in 0..steps {
+            x.set_grad(0.0);
+            (&x + -3.0).power(2.0).backward();
+            opt.step(std::slice::from_ref(&x));
+        }
+        x.get_data()
+    }
+
+    #[test]
+    fn test_sgd() {
+        let mut opt = Sgd::new(0.1);
+        assert_eq!((minimize(&mut opt, 1) * 1_000_f64).round() / 1_000_f64, 0.6);
+        assert!((minimize(&mut opt, 100) - 3.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_momentum() {
+        let mut opt = Momentum::new(0.05);
+        assert!((minimize(&mut opt, 300) - 3.0).abs() < 1e-4);
+    }
+
+    #[test]
+    fn test_adam() {
+        // the first step moves every parameter by about lr, whatever the gradient's size
+        let mut opt = Adam::new(0.1);
+        assert!((minimize(&mut opt, 1) - 0.1).abs() < 1e-6);
+        let mut opt = Adam::new(0.1);
+        assert!((minimize(&mut opt, 500) - 3.0).abs() < 1e-2);
+    }
+
+    #[test]
+    fn test_by_name() {
+        for name in NAMES.iter() {
+            assert_eq!(by_name(name, 0.5).unwrap().lr(), 0.5);
+        }
+        assert!(by_name("rmsprop", 0.5).is_none());
+    }
+}
@@ -0,0 +1,170 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+use serde::{Serialize, Deserialize};
+use super::engine::no_grad;
+use super::nn::{Activation, Base, Model};
+use super::preprocessing::{Pipeline, Transformer};
+use super::error::OxiError;
+
+// MODEL EXPORT IMPLEMENTATION
+// everything needed to rebuild a trained model (architecture and parameters) together with
+// the preprocessing fitted at training time, saved as a JSON file
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct SavedModel {
+    pub input_size: usize,
+    pub arch: Vec<usize>,
+    // files saved before activations were configurable have none, i.e. the defaults of Model::new
+    #[serde(default)]
+    pub activations: Vec<Activation>,
+    // in the same order as Base::params
+    pub params: Vec<f64>,
+    pub pipeline: Pipeline,
+}
+
+impl SavedModel {
+    pub fn new(model: &Model, pipeline: &Pipeline) -> Self {
+        SavedModel {
+            input_size: model.input_size(),
+            arch: model.arch(),
+            activations: model.activations(),
+            params: model.params().iter().map(|p| p.get_data()).collect(),
+            pipeline: pipeline.clone(),
+        }
+    }
+
+    // rebuild the model with the saved parameters, errors being the ones of Model::try_with_activations
+    pub fn model(&self) -> Result<Model, OxiError> {
+        let m = if self.activations.is_empty() {
+            Model::try_new(self.input_size, &self.arch)?
+        } else {
+            Model::try_with_activations(self.input_size, &self.arch, &self.activations)?
+        };
+        for (p, v) in m.params().iter().zip(self.params.iter()) {
+            p.set_data(*v);
+        }
+
+        Ok(m)
+    }
+
+    // features per raw input (i.e. before preprocessing), None if the pipeline doesn't produce the
+    // model's inputs (not the case for files read by load)
+    pub fn features(&self) -> Option<usize> {
+        self.pipeline.input_width_for(self.input_size)
+    }
+
+    // raw inputs are preprocessed exactly as the training data was, after checking their width
+    // (the preprocessors would index out of bounds and the model ignore extra inputs otherwise)
+    pub fn predict(&self, model: &Model, inputs: &[f64]) -> Result<f64, OxiError> {
+        if let Some(n) = self.features().filter(|n| *n != inputs.len()) {
+            return Err(OxiError::DimensionMismatch { expected: n, found: inputs.len() });
+        }
+
+        let _guard = no_grad();
+        Ok(model.try_forward(&self.pipeline.transform_one(inputs))?.get_data())
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        fs::write(path, serde_json::to_string_pretty(self)?)
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let saved: SavedModel = serde_json::from_str(&fs::read_to_string(path)?)?;
+
+        let expected = saved.arch.iter()
+            .scan(saved.input_size, |inputs, n| { let p = (*inputs + 1) * n; *inputs = *n; Some(p) })
+            .sum::<usize>();
+        if !saved.activations.is_empty() && saved.activations.len() != saved.arch.len() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("expected {} activations for the saved architecture, found {}", saved.arch.len(), saved.activations.len()),
+            ));
+        }
+        if saved.params.len() != expected {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("expected {} parameters for the saved architecture, found {}", expected, saved.params.len()),
+            ));
+        }
+        if saved.features().is_none() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("the saved preprocessing doesn't produce the {} inputs of the model", saved.input_size),
+            ));
+        }
+
+        Ok(saved)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use super::super::preprocessing::{Preprocessor, StandardScaler, OneHotEncoder, LabelEncoder, LabelMode};
+
+    #[test]
+    fn test_save_load() {
+        let data = vec![vec![1.0, 2.0], vec![3.0, 6.0], vec![5.0, 4.0]];
+        let mut pipeline = Pipeline::new(vec![Preprocessor::Standard(StandardScaler::new())]);
+        pipeline.fit(&data);
+        let mut labels = LabelEncoder::new(LabelMode::Signed);
+        labels.fit(&["cat", "dog"]).unwrap();
+        pipeline.labels = Some(labels);
+
+        let m = Model::new(2, &[4, 1]);
+        let saved = SavedModel::new(&m, &pipeline);
+        let path = std::env::temp_dir().join("oxigrad_test_save_load.json");
+        saved.save(&path).unwrap();
+        let loaded = SavedModel::load(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(loaded, saved);
+        assert_eq!(loaded.features(), Some(2));
+        let restored = loaded.model().unwrap();
+        assert_eq!(restored.arch(), vec![4, 1]);
+        for x in data.iter() {
+            assert_eq!(
+                loaded.predict(&restored, x).unwrap(),
+                m.forward(&pipeline.transform_one(x)).get_data(),
+            );
+        }
+        assert!(matches!(loaded.predict(&restored, &[1.0]), Err(OxiError::DimensionMismatch { expected: 2, found: 1 })));
+    }
+
+    #[test]
+    fn test_predict_one_hot() {
+        // a pipeline starting with a one-hot encoder, whose input width is worked out from the model's
+        let data = vec![vec![0.0, 1.0], vec![1.0, 2.0], vec![2.0, 3.0]];
+        let mut pipeline = Pipeline::new(vec![
+            Preprocessor::OneHot(OneHotEncoder::new(vec![0])),
+            Preprocessor::Standard(StandardScaler::new()),
+        ]);
+        let x = pipeline.fit_transform(&data);
+        let m = Model::new(x[0].len(), &[2, 1]);
+        let saved = SavedModel::new(&m, &pipeline);
+
+        assert_eq!(saved.features(), Some(2));
+        assert!(saved.predict(&m, &[1.0, 2.0]).is_ok());
+        for row in [&[1.0][..], &[1.0, 2.0, 3.0]] {
+            assert!(matches!(saved.predict(&m, row), Err(OxiError::DimensionMismatch { expected: 2, .. })));
+        }
+
+        // not matching the model's inputs
+        let small = Model::new(3, &[1]);
+        let other = SavedModel::new(&small, &pipeline);
+        assert_eq!(other.features(), None);
+        assert!(matches!(other.predict(&small, &[1.0, 2.0]), Err(OxiError::DimensionMismatch { expected: 3, found: 4 })));
+    }
+
+    #[test]
+    fn test_load_mismatch() {
+        let mut saved = SavedModel::new(&Model::new(2, &[3, 1]), &Pipeline::default());
+        saved.params.pop();
+        let path = std::env::temp_dir().join("oxigrad_test_load_mismatch.json");
+        saved.save(&path).unwrap();
+        let loaded = SavedModel::load(&path);
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(loaded.unwrap_err().kind(), io::ErrorKind::InvalidData);
+    }
+}
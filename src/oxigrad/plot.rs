@@ -0,0 +1,206 @@
+use super::engine::no_grad;
+use super::nn::Model;
+
+// GRID IMPLEMENTATION
+// model outputs evaluated at the center of each cell of a grid covering the 2-D data
+// (with a small margin), row 0 being the top one (i.e. the highest y)
+pub struct Grid {
+    pub x_range: (f64, f64),
+    pub y_range: (f64, f64),
+    pub cols: usize,
+    pub rows: usize,
+    pub values: Vec<Vec<f64>>,
+}
+
+impl Grid {
+    pub fn evaluate(model: &Model, data: &[Vec<f64>], cols: usize, rows: usize) -> Self {
+        assert_eq!(model.input_size(), 2, "Decision boundaries can only be drawn for models with 2 inputs");
+        let (cols, rows) = (cols.max(1), rows.max(1));
+
+        let bounds = |c: usize| {
+            let (lo, hi) = data.iter()
+                .map(|r| r[c])
+                .fold((f64::INFINITY, f64::NEG_INFINITY), |(lo, hi), v| (lo.min(v), hi.max(v)));
+            let margin = if hi > lo { (hi - lo) * 0.1 } else { 1.0 };
+            (lo - margin, hi + margin)
+        };
+        let (x_range, y_range) = (bounds(0), bounds(1));
+
+        let mut g = Grid {
+            x_range,
+            y_range,
+            cols,
+            rows,
+            values: Vec::with_capacity(rows),
+        };
+        let _guard = no_grad();
+        g.values = (0..rows)
+            .map(|r| (0..cols).map(|c| model.forward(&g.center(r, c)).get_data()).collect())
+            .collect();
+
+        g
+    }
+
+    // data coordinates of the center of a cell
+    pub fn center(&self, row: usize, col: usize) -> [f64; 2] {
+        let (x0, x1) = self.x_range;
+        let (y0, y1) = self.y_range;
+        [
+            x0 + (col as f64 + 0.5) * (x1 - x0) / self.cols as f64,
+            y1 - (row as f64 + 0.5) * (y1 - y0) / self.rows as f64,
+        ]
+    }
+
+    // cell containing a point, if it's inside the grid
+    pub fn cell(&self, point: &[f64]) -> Option<(usize, usize)> {
+        let (x0, x1) = self.x_range;
+        let (y0, y1) = self.y_range;
+        let col = ((point[0] - x0) / (x1 - x0) * self.cols as f64).floor();
+        let row = ((y1 - point[1]) / (y1 - y0) * self.rows as f64).floor();
+
+        if col < 0.0 || row < 0.0 || col >= self.cols as f64 || row >= self.rows as f64 {
+            None
+        } else {
+            Some((row as usize, col as usize))
+        }
+    }
+}
+
+// LOSS CURVE
+// line chart of the values (e.g. History::series("loss")) with the y range on the left
+// NaNs and infinities are left as gaps, so that every other value stays at its own pass
+pub fn loss_curve(values: &[f64], width: usize, height: usize) -> String {
+    let finite = values.iter().cloned().filter(|v| v.is_finite());
+    let (lo, hi) = finite.fold((f64::INFINITY, f64::NEG_INFINITY), |(lo, hi), v| (lo.min(v), hi.max(v)));
+    if lo > hi {
+        return String::from("(no values to plot)\n");
+    }
+
+    let (width, height) = (width.max(2), height.max(2));
+    let span = if hi > lo { hi - lo } else { 1.0 };
+
+    // each column shows the value closest to its position along the training
+    let mut canvas = vec![vec![' '; width]; height];
+    let mut prev: Option<usize> = None;
+    for (c, col) in (0..width).map(|c| (c, c * (values.len() - 1) / (width - 1))) {
+        if !values[col].is_finite() {
+            prev = None;
+            continue;
+        }
+        let row = ((hi - values[col]) / span * (height - 1) as f64).round() as usize;
+
+        // vertical segments keep the curve connected on steep slopes
+        if let Some(p) = prev {
+            let (from, to) = if p < row { (p + 1, row) } else { (row + 1, p) };
+            for line in canvas.iter_mut().take(to).skip(from) {
+                line[c] = '│';
+            }
+        }
+        canvas[row][c] = '•';
+        prev = Some(row);
+    }
+
+    let label_width = format!("{:.4}", hi).len().max(format!("{:.4}", lo).len());
+    let mut out = String::new();
+    for (r, line) in canvas.iter().enumerate() {
+        let label = match r {
+            0 => format!("{:.4}", hi),
+            r if r == height - 1 => format!("{:.4}", lo),
+            _ => String::new(),
+        };
+        out.push_str(&format!("{:>w$} ┤{}\n", label, line.iter().collect::<String>(), w = label_width));
+    }
+    out.push_str(&format!("{:>w$} └{}\n", "", "─".repeat(width), w = label_width));
+    out.push_str(&format!("{:>w$}  0{:>p$}\n", "", values.len() - 1, w = label_width, p = width - 1));
+
+    out
+}
+
+// DECISION BOUNDARY
+// regions where the model predicts a positive (▒) or a negative (░) label,
+// with the data points overlaid (+ for positive labels, o for negative ones)
+pub fn decision_boundary(model: &Model, data: &[Vec<f64>], labels: &[f64], width: usize, height: usize) -> String {
+    let grid = Grid::evaluate(model, data, width, height);
+
+    let mut canvas: Vec<Vec<char>> = grid.values.iter()
+        .map(|r| r.iter().map(|v| if *v > 0.0 { '▒' } else { '░' }).collect())
+        .collect();
+    for (point, label) in data.iter().zip(labels.iter()) {
+        if let Some((r, c)) = grid.cell(point) {
+            canvas[r][c] = if *label > 0.0 { '+' } else { 'o' };
+        }
+    }
+
+    let mut out = String::new();
+    for line in canvas.iter() {
+        out.push_str(&line.iter().collect::<String>());
+        out.push('\n');
+    }
+    out.push_str(&format!(
+        "x=[{:.2}, {:.2}] y=[{:.2}, {:.2}]  ▒ positive  ░ negative  + positive label  o negative label\n",
+        grid.x_range.0, grid.x_range.1, grid.y_range.0, grid.y_range.1,
+    ));
+
+    out
+}
+
+// model predicting the value of its first input (x > 0 is the positive region), also used by image's tests
+#[cfg(test)]
+pub(crate) fn identity_x() -> Model {
+    use super::nn::Base;
+
+    let m = Model::new(2, &[1]);
+    let params = m.params();
+    params[0].set_data(1.0);
+    params[1].set_data(0.0);
+    params[2].set_data(0.0);
+    m
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_grid() {
+        let data = vec![vec![-1.0, -1.0], vec![1.0, 1.0]];
+        let g = Grid::evaluate(&identity_x(), &data, 4, 2);
+
+        assert_eq!(g.x_range, (-1.2, 1.2));
+        assert_eq!(g.values.len(), 2);
+        assert!(g.values.iter().all(|r| r[0] < 0.0 && r[3] > 0.0));
+        assert_eq!(g.cell(&[1.0, 1.0]), Some((0, 3)));
+        assert_eq!(g.cell(&[-1.0, -1.0]), Some((1, 0)));
+        assert_eq!(g.cell(&[5.0, 0.0]), None);
+    }
+
+    #[test]
+    fn test_decision_boundary() {
+        let data = vec![vec![-1.0, 0.0], vec![1.0, 0.0]];
+        let plot = decision_boundary(&identity_x(), &data, &[-1.0, 1.0], 10, 3);
+        let lines: Vec<&str> = plot.lines().collect();
+
+        assert_eq!(lines.len(), 4);
+        assert_eq!(lines[0], "░░░░░▒▒▒▒▒");
+        assert_eq!(lines[1], "o░░░░▒▒▒▒+");
+    }
+
+    #[test]
+    fn test_loss_curve() {
+        let plot = loss_curve(&[4.0, 3.0, 2.0, 1.0, 0.0], 5, 5);
+        let lines: Vec<&str> = plot.lines().collect();
+
+        assert_eq!(lines.len(), 7);
+        assert_eq!(lines[0], "4.0000 ┤•    ");
+        assert_eq!(lines[4], "0.0000 ┤    •");
+        assert!(lines[6].ends_with(" 0   4"));
+        assert_eq!(loss_curve(&[f64::NAN], 5, 5), "(no values to plot)\n");
+
+        // a gap where the loss wasn't finite, the passes after it keep their column
+        let plot = loss_curve(&[4.0, f64::NAN, 2.0, 1.0, 0.0], 5, 5);
+        let lines: Vec<&str> = plot.lines().collect();
+        assert_eq!(lines[0], "4.0000 ┤•    ");
+        assert_eq!(lines[2], "       ┤  •  ");
+        assert!(lines.iter().all(|l| l.chars().nth(9) != Some('•')));
+    }
+}
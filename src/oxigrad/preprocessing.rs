@@ -0,0 +1,473 @@
+use std::fmt::Display;
+use serde::{Serialize, Deserialize};
+use super::error::OxiError;
+
+// TRANSFORMER TRAIT
+// statistics are learned with fit (on training data only) and then applied as they are
+// by transform, so that inference sees exactly the same transformation used in training
+pub trait Transformer {
+    fn fit(&mut self, data: &[Vec<f64>]);
+
+    fn transform_one(&self, row: &[f64]) -> Vec<f64>;
+
+    fn transform(&self, data: &[Vec<f64>]) -> Vec<Vec<f64>> {
+        data.iter().map(|r| self.transform_one(r)).collect()
+    }
+
+    fn fit_transform(&mut self, data: &[Vec<f64>]) -> Vec<Vec<f64>> {
+        self.fit(data);
+        self.transform(data)
+    }
+}
+
+// values of a single column, as fit works column by column
+fn column(data: &[Vec<f64>], c: usize) -> Vec<f64> {
+    data.iter().map(|r| r[c]).collect()
+}
+
+fn width(data: &[Vec<f64>]) -> usize {
+    data.first().map_or(0, |r| r.len())
+}
+
+// linear interpolation between the closest ranks of the sorted values
+fn quantile(sorted: &[f64], q: f64) -> f64 {
+    if sorted.is_empty() {
+        return 0.0;
+    }
+
+    let pos = q * (sorted.len() - 1) as f64;
+    let (lo, hi) = (pos.floor() as usize, pos.ceil() as usize);
+    sorted[lo] + (sorted[hi] - sorted[lo]) * (pos - lo as f64)
+}
+
+// constant columns would lead to a division by zero, so they're left unscaled
+fn non_zero(scale: f64) -> f64 {
+    if scale.abs() < f64::EPSILON { 1.0 } else { scale }
+}
+
+// STANDARD SCALER IMPLEMENTATION
+// zero mean and unit variance for each column
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct StandardScaler {
+    pub mean: Vec<f64>,
+    pub std: Vec<f64>,
+}
+
+impl StandardScaler {
+    pub fn new() -> Self {
+        StandardScaler::default()
+    }
+}
+
+impl Transformer for StandardScaler {
+    fn fit(&mut self, data: &[Vec<f64>]) {
+        let n = data.len() as f64;
+        self.mean = (0..width(data))
+            .map(|c| column(data, c).iter().sum::<f64>() / n)
+            .collect();
+        self.std = (0..width(data))
+            .map(|c| (column(data, c).iter().map(|x| (x - self.mean[c]).powi(2)).sum::<f64>() / n).sqrt())
+            .collect();
+    }
+
+    fn transform_one(&self, row: &[f64]) -> Vec<f64> {
+        row.iter()
+            .enumerate()
+            .map(|(c, x)| (x - self.mean[c]) / non_zero(self.std[c]))
+            .collect()
+    }
+}
+
+// MIN-MAX SCALER IMPLEMENTATION
+// each column linearly mapped to the given range (default [0, 1])
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct MinMaxScaler {
+    pub range: (f64, f64),
+    pub min: Vec<f64>,
+    pub max: Vec<f64>,
+}
+
+impl Default for MinMaxScaler {
+    fn default() -> Self {
+        MinMaxScaler::new(0.0, 1.0)
+    }
+}
+
+impl MinMaxScaler {
+    pub fn new(low: f64, high: f64) -> Self {
+        MinMaxScaler {
+            range: (low, high),
+            min: Vec::new(),
+            max: Vec::new(),
+        }
+    }
+}
+
+impl Transformer for MinMaxScaler {
+    fn fit(&mut self, data: &[Vec<f64>]) {
+        self.min = (0..width(data))
+            .map(|c| column(data, c).into_iter().fold(f64::INFINITY, f64::min))
+            .collect();
+        self.max = (0..width(data))
+            .map(|c| column(data, c).into_iter().fold(f64::NEG_INFINITY, f64::max))
+            .collect();
+    }
+
+    fn transform_one(&self, row: &[f64]) -> Vec<f64> {
+        let (low, high) = self.range;
+        row.iter()
+            .enumerate()
+            .map(|(c, x)| low + (x - self.min[c]) / non_zero(self.max[c] - self.min[c]) * (high - low))
+            .collect()
+    }
+}
+
+// ROBUST SCALER IMPLEMENTATION
+// median removed and scaled by the interquartile range, so that outliers have little influence
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct RobustScaler {
+    pub median: Vec<f64>,
+    pub iqr: Vec<f64>,
+}
+
+impl RobustScaler {
+    pub fn new() -> Self {
+        RobustScaler::default()
+    }
+}
+
+impl Transformer for RobustScaler {
+    fn fit(&mut self, data: &[Vec<f64>]) {
+        let sorted: Vec<Vec<f64>> = (0..width(data))
+            .map(|c| {
+                let mut col = column(data, c);
+                col.sort_by(|a, b| a.total_cmp(b));
+                col
+            })
+            .collect();
+
+        self.median = sorted.iter().map(|col| quantile(col, 0.5)).collect();
+        self.iqr = sorted.iter().map(|col| quantile(col, 0.75) - quantile(col, 0.25)).collect();
+    }
+
+    fn transform_one(&self, row: &[f64]) -> Vec<f64> {
+        row.iter()
+            .enumerate()
+            .map(|(c, x)| (x - self.median[c]) / non_zero(self.iqr[c]))
+            .collect()
+    }
+}
+
+// ONE-HOT ENCODER IMPLEMENTATION
+// each of the given categorical columns is replaced (in place) by one column per category seen in fit,
+// categories not seen in fit are encoded with all zeros
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct OneHotEncoder {
+    pub columns: Vec<usize>,
+    pub categories: Vec<Vec<f64>>,
+}
+
+impl OneHotEncoder {
+    pub fn new(columns: Vec<usize>) -> Self {
+        OneHotEncoder {
+            columns,
+            categories: Vec::new(),
+        }
+    }
+}
+
+impl Transformer for OneHotEncoder {
+    fn fit(&mut self, data: &[Vec<f64>]) {
+        self.categories = self.columns.iter()
+            .map(|c| {
+                let mut cats = column(data, *c);
+                cats.sort_by(|a, b| a.total_cmp(b));
+                cats.dedup();
+                cats
+            })
+            .collect();
+    }
+
+    fn transform_one(&self, row: &[f64]) -> Vec<f64> {
+        let mut out = Vec::with_capacity(row.len());
+
+        for (c, x) in row.iter().enumerate() {
+            match self.columns.iter().position(|col| *col == c) {
+                Some(i) => out.extend(self.categories[i].iter().map(|cat| if cat == x { 1.0 } else { 0.0 })),
+                None => out.push(*x),
+            }
+        }
+
+        out
+    }
+}
+
+// LABEL ENCODER IMPLEMENTATION
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum LabelMode {
+    // two classes only, mapped to -1.0 and 1.0 (as in LBLS_DATASET)
+    Signed,
+    // any number of classes, mapped to their index
+    Index,
+}
+
+// maps arbitrary labels (anything printable, e.g. numbers or strings) to numeric targets
+// classes are sorted (by value when they're all numbers), so that the same labels always get the same encoding
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct LabelEncoder {
+    pub mode: LabelMode,
+    pub classes: Vec<String>,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct UnknownLabel(pub String);
+
+impl Display for UnknownLabel {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "label '{}' was not seen when fitting the encoder", self.0)
+    }
+}
+
+impl std::error::Error for UnknownLabel {}
+
+impl LabelEncoder {
+    pub fn new(mode: LabelMode) -> Self {
+        LabelEncoder {
+            mode,
+            classes: Vec::new(),
+        }
+    }
+
+    // signed encoding needs exactly two classes, the encoder is left as it was otherwise
+    pub fn fit<L: ToString>(&mut self, labels: &[L]) -> Result<(), OxiError> {
+        let mut classes: Vec<String> = labels.iter().map(|l| l.to_string()).collect();
+        let numbers: Option<Vec<f64>> = classes.iter().map(|c| c.parse::<f64>().ok()).collect();
+        match numbers {
+            Some(n) if n.iter().all(|v| !v.is_nan()) => {
+                let mut pairs: Vec<(f64, String)> = n.into_iter().zip(classes).collect();
+                pairs.sort_by(|a, b| a.0.total_cmp(&b.0).then_with(|| a.1.cmp(&b.1)));
+                classes = pairs.into_iter().map(|(_, c)| c).collect();
+            },
+            _ => classes.sort(),
+        }
+        classes.dedup();
+
+        if self.mode == LabelMode::Signed && classes.len() != 2 {
+            return Err(OxiError::ClassCount { expected: 2, found: classes.len() });
+        }
+
+        self.classes = classes;
+        Ok(())
+    }
+
+    pub fn encode<L: ToString>(&self, label: &L) -> Result<f64, UnknownLabel> {
+        let label = label.to_string();
+        let index = self.classes.iter()
+            .position(|c| *c == label)
+            .ok_or(UnknownLabel(label))?;
+
+        Ok(match self.mode {
+            LabelMode::Signed => if index == 0 { -1.0 } else { 1.0 },
+            LabelMode::Index => index as f64,
+        })
+    }
+
+    pub fn transform<L: ToString>(&self, labels: &[L]) -> Result<Vec<f64>, UnknownLabel> {
+        labels.iter().map(|l| self.encode(l)).collect()
+    }
+
+    // label associated with a prediction (its sign or its closest index), None before fitting
+    pub fn decode(&self, prediction: f64) -> Option<&str> {
+        let last = self.classes.len().checked_sub(1)?;
+        let index = match self.mode {
+            LabelMode::Signed => usize::from(prediction > 0.0),
+            LabelMode::Index => (prediction.round().max(0.0) as usize).min(last),
+        };
+
+        self.classes.get(index).map(|c| c.as_str())
+    }
+}
+
+// PIPELINE IMPLEMENTATION
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Preprocessor {
+    Standard(StandardScaler),
+    MinMax(MinMaxScaler),
+    Robust(RobustScaler),
+    OneHot(OneHotEncoder),
+}
+
+impl Transformer for Preprocessor {
+    fn fit(&mut self, data: &[Vec<f64>]) {
+        match self {
+            Preprocessor::Standard(t) => t.fit(data),
+            Preprocessor::MinMax(t) => t.fit(data),
+            Preprocessor::Robust(t) => t.fit(data),
+            Preprocessor::OneHot(t) => t.fit(data),
+        }
+    }
+
+    fn transform_one(&self, row: &[f64]) -> Vec<f64> {
+        match self {
+            Preprocessor::Standard(t) => t.transform_one(row),
+            Preprocessor::MinMax(t) => t.transform_one(row),
+            Preprocessor::Robust(t) => t.transform_one(row),
+            Preprocessor::OneHot(t) => t.transform_one(row),
+        }
+    }
+}
+
+// sequence of preprocessors (each one fitted on the output of the previous one)
+// plus an optional label encoder, all saved together with the model
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Pipeline {
+    pub steps: Vec<Preprocessor>,
+    pub labels: Option<LabelEncoder>,
+}
+
+impl Pipeline {
+    pub fn new(steps: Vec<Preprocessor>) -> Self {
+        Pipeline {
+            steps,
+            labels: None,
+        }
+    }
+
+    // features per row the fitted pipeline expects, None when there are no steps
+    // or the first one can't tell (a one-hot encoder only knows its categorical columns)
+    pub fn input_width(&self) -> Option<usize> {
+        match self.steps.first()? {
+            Preprocessor::Standard(t) => Some(t.mean.len()),
+            Preprocessor::MinMax(t) => Some(t.min.len()),
+            Preprocessor::Robust(t) => Some(t.median.len()),
+            Preprocessor::OneHot(_) => None,
+        }
+    }
+
+    // features per row the fitted pipeline expects to produce output features (e.g. a model's inputs),
+    // worked out backwards through the steps, None if they don't agree with it or aren't fitted
+    pub fn input_width_for(&self, output: usize) -> Option<usize> {
+        self.steps.iter().rev().try_fold(output, |width, step| match step {
+            Preprocessor::Standard(t) => (t.mean.len() == width).then_some(width),
+            Preprocessor::MinMax(t) => (t.min.len() == width).then_some(width),
+            Preprocessor::Robust(t) => (t.median.len() == width).then_some(width),
+            // each categorical column turns into a column per category
+            Preprocessor::OneHot(t) => {
+                let added = t.categories.iter().map(|c| c.len()).sum::<usize>();
+                if t.categories.len() != t.columns.len() {
+                    return None;
+                }
+                (width + t.columns.len()).checked_sub(added).filter(|w| t.columns.iter().all(|c| c < w))
+            },
+        })
+    }
+}
+
+impl Transformer for Pipeline {
+    fn fit(&mut self, data: &[Vec<f64>]) {
+        let mut data = data.to_vec();
+        for step in self.steps.iter_mut() {
+            data = step.fit_transform(&data);
+        }
+    }
+
+    fn transform_one(&self, row: &[f64]) -> Vec<f64> {
+        self.steps.iter().fold(row.to_vec(), |r, step| step.transform_one(&r))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn data() -> Vec<Vec<f64>> {
+        vec![
+            vec![1.0, 10.0, 0.0],
+            vec![2.0, 10.0, 2.0],
+            vec![3.0, 10.0, 1.0],
+            vec![4.0, 10.0, 2.0],
+            vec![100.0, 10.0, 0.0],
+        ]
+    }
+
+    #[test]
+    fn test_standard_scaler() {
+        let mut s = StandardScaler::new();
+        let out = s.fit_transform(&data());
+
+        let mean = out.iter().map(|r| r[0]).sum::<f64>() / 5.0;
+        let var = out.iter().map(|r| r[0].powi(2)).sum::<f64>() / 5.0;
+        assert!(mean.abs() < 1e-12);
+        assert!((var - 1.0).abs() < 1e-12);
+
+        // constant columns are only centered
+        assert!(out.iter().all(|r| r[1] == 0.0));
+    }
+
+    #[test]
+    fn test_min_max_scaler() {
+        let mut s = MinMaxScaler::new(-1.0, 1.0);
+        let out = s.fit_transform(&data());
+
+        assert_eq!(out[0][0], -1.0);
+        assert_eq!(out[4][0], 1.0);
+        assert_eq!(out[1][2], 1.0);
+
+        // statistics from fit are kept for unseen data
+        assert_eq!(s.transform_one(&[199.0, 10.0, 4.0]), vec![3.0, -1.0, 3.0]);
+    }
+
+    #[test]
+    fn test_robust_scaler() {
+        let mut s = RobustScaler::new();
+        s.fit(&data());
+
+        // the outlier doesn't change median (3.0) and iqr (4.0 - 2.0)
+        assert_eq!(s.median[0], 3.0);
+        assert_eq!(s.iqr[0], 2.0);
+        assert_eq!(s.transform_one(&[5.0, 10.0, 1.0])[0], 1.0);
+    }
+
+    #[test]
+    fn test_one_hot_encoder() {
+        let mut e = OneHotEncoder::new(vec![2]);
+        let out = e.fit_transform(&data());
+
+        assert_eq!(e.categories, vec![vec![0.0, 1.0, 2.0]]);
+        assert_eq!(out[1], vec![2.0, 10.0, 0.0, 0.0, 1.0]);
+        assert_eq!(e.transform_one(&[0.0, 0.0, 7.0]), vec![0.0, 0.0, 0.0, 0.0, 0.0]);
+    }
+
+    #[test]
+    fn test_label_encoder() {
+        let mut e = LabelEncoder::new(LabelMode::Signed);
+        assert_eq!(e.decode(0.3), None);
+        e.fit(&["yes", "no", "yes"]).unwrap();
+        assert_eq!(e.transform(&["no", "yes"]), Ok(vec![-1.0, 1.0]));
+        assert_eq!(e.decode(0.3), Some("yes"));
+        assert_eq!(e.encode(&"maybe"), Err(UnknownLabel("maybe".to_string())));
+        assert!(matches!(e.fit(&["a", "b", "c"]), Err(OxiError::ClassCount { expected: 2, found: 3 })));
+        assert_eq!(e.classes, vec!["no", "yes"]);
+
+        // numbers are sorted by value, not as strings
+        let mut e = LabelEncoder::new(LabelMode::Index);
+        e.fit(&[10.0, 7.0, 9.0, 10.0]).unwrap();
+        assert_eq!(e.transform(&[7.0, 9.0, 10.0]), Ok(vec![0.0, 1.0, 2.0]));
+        assert_eq!(e.decode(1.8), Some("10"));
+    }
+
+    #[test]
+    fn test_pipeline() {
+        let mut p = Pipeline::new(vec![
+            Preprocessor::OneHot(OneHotEncoder::new(vec![2])),
+            Preprocessor::MinMax(MinMaxScaler::default()),
+        ]);
+        let out = p.fit_transform(&data());
+        assert!(out.iter().all(|r| r.len() == 5 && r.iter().all(|x| (0.0..=1.0).contains(x))));
+
+        // fitted statistics survive serialization
+        let json = serde_json::to_string(&p).unwrap();
+        let restored: Pipeline = serde_json::from_str(&json).unwrap();
+        assert_eq!(restored.transform(&data()), out);
+    }
+}
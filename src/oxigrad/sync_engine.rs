@@ -0,0 +1,494 @@
+// thread-safe counterpart of the engine module: the same graph and operators but built on
+// Arc, RwLock and atomics, so that values (and models built on them) are Send + Sync
+use std::ops;
+use std::sync::{Arc, RwLock};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::collections::HashSet;
+use std::hash::{Hash, Hasher};
+use std::fmt::{Debug, Display};
+use super::engine::{Backend, Operation};
+use super::error::OxiError;
+
+// f64 stored as its bit pattern inside an AtomicU64
+#[derive(Debug, Default)]
+pub struct AtomicF64(AtomicU64);
+
+impl AtomicF64 {
+    pub fn new(val: f64) -> Self {
+        AtomicF64(AtomicU64::new(val.to_bits()))
+    }
+
+    pub fn get(&self) -> f64 {
+        f64::from_bits(self.0.load(Ordering::SeqCst))
+    }
+
+    pub fn set(&self, val: f64) {
+        self.0.store(val.to_bits(), Ordering::SeqCst);
+    }
+
+    // atomic read-modify-write, so that concurrent backward passes sharing some nodes
+    // (e.g. the parameters of a model) don't lose gradient contributions
+    pub fn add(&self, val: f64) {
+        let _ = self.0.fetch_update(Ordering::SeqCst, Ordering::SeqCst, |bits| {
+            Some((f64::from_bits(bits) + val).to_bits())
+        });
+    }
+}
+
+pub struct SyncCore {
+    pub data: Arc<AtomicF64>,
+    pub grad: Arc<AtomicF64>,
+    op: Option<Operation>,
+    pub children: Option<Vec<SyncValue>>,
+    backward: Option<Box<dyn Fn() + Send + Sync>>,
+}
+
+impl Debug for SyncCore {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SYNC CORE")
+            .field("DATA", &self.data.get())
+            .field("GRAD", &self.grad.get())
+            .field("CHILDREN", &self.children)
+            .finish()
+    }
+}
+
+pub trait SyncValueConstructors {
+    fn construct(self) -> SyncValue;
+}
+
+// constructor requiring fields: data
+impl SyncValueConstructors for f64 {
+    fn construct(self) -> SyncValue {
+        (self, 0.0, None, None).construct()
+    }
+}
+
+// constructor requiring fields: data, grad
+impl SyncValueConstructors for (f64, f64) {
+    fn construct(self) -> SyncValue {
+        (self.0, self.1, None, None).construct()
+    }
+}
+
+// constructor requiring fields: data, op
+impl SyncValueConstructors for (f64, Option<Operation>) {
+    fn construct(self) -> SyncValue {
+        (self.0, 0.0, self.1, None).construct()
+    }
+}
+
+// constructor requiring fields: data, op, children
+impl SyncValueConstructors for (f64, Option<Operation>, Option<Vec<SyncValue>>) {
+    fn construct(self) -> SyncValue {
+        (self.0, 0.0, self.1, self.2).construct()
+    }
+}
+
+// constructor requiring fields: data, grad, op, children
+impl SyncValueConstructors for (f64, f64, Option<Operation>, Option<Vec<SyncValue>>) {
+    fn construct(self) -> SyncValue {
+        SyncValue {
+            core: Arc::new(RwLock::new(SyncCore {
+                data: Arc::new(AtomicF64::new(self.0)),
+                grad: Arc::new(AtomicF64::new(self.1)),
+                op: self.2,
+                children: self.3,
+                backward: None,
+            }))
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct SyncValue {
+    pub core: Arc<RwLock<SyncCore>>,
+}
+
+impl SyncValue {
+    pub fn new<V>(args: V) -> SyncValue
+        where V: SyncValueConstructors
+    {
+        args.construct()
+    }
+
+    // the lock is only ever held for short, non-reentrant sections, so a poisoned lock
+    // still guards consistent data (the numbers themselves live in atomics)
+    fn read(&self) -> std::sync::RwLockReadGuard<'_, SyncCore> {
+        self.core.read().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn set_backward(&self, back: Box<dyn Fn() + Send + Sync>) {
+        self.core.write().unwrap_or_else(|e| e.into_inner()).backward = Some(back);
+    }
+
+    // panics where try_backward returns an error
+    pub fn backward(&self) {
+        if let Err(e) = self.try_backward() {
+            panic!("{}", e);
+        }
+    }
+
+    // nodes are hashed by pointer, so their interior mutability doesn't affect the visited set
+    // (on errors the gradients of the nodes already visited are left updated)
+    #[allow(clippy::mutable_key_type)]
+    pub fn try_backward(&self) -> Result<(), OxiError> {
+        let mut tp_order: Vec<SyncValue> = vec![];
+        let mut visited = HashSet::new();
+
+        fn topological_sort(node: &SyncValue, visited: &mut HashSet<SyncValue>, tp_order: &mut Vec<SyncValue>) {
+            if !visited.contains(node) {
+                visited.insert(node.clone());
+
+                if let Some(v) = node.read().children.as_ref() {
+                    for c in v.iter() {
+                        topological_sort(c, visited, tp_order);
+                    }
+                    tp_order.push(node.clone());
+                }
+            }
+        }
+
+        // topological sort of graph's nodes
+        topological_sort(self, &mut visited, &mut tp_order);
+
+        // a derivative of something (i.e. the starting node for the backward pass) w.r.t itself is 1
+        self.set_grad(1.0);
+
+        // backward pass on reversed topological order
+        for v in tp_order.iter().rev() {
+            let core = v.read();
+            match core.backward.as_ref() {
+                Some(back) => back(),
+                None => return Err(OxiError::MissingBackward { op: core.op }),
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn power(&self, exp: f64) -> Self {
+        let out = SyncValue::new((
+            self.get_data().powf(exp),
+            Some(Operation::Power),
+            Some(vec![self.clone()]),
+        ));
+
+        let s_grad = self.read().grad.clone();
+        let out_grad = out.read().grad.clone();
+        let s_data = self.read().data.clone();
+
+        // derivative for raise to the power operation
+        out.set_backward(Box::new(move || {
+            s_grad.add(exp * (s_data.get().powf(exp - 1.0)) * out_grad.get());
+        }));
+
+        out
+    }
+
+    pub fn relu(&self) -> Self {
+        let data = if self.get_data() >= 0.0 { self.get_data() } else { 0.0 };
+        let out = SyncValue::new((
+            data,
+            Some(Operation::ReLU),
+            Some(vec![self.clone()]),
+        ));
+
+        let s_grad = self.read().grad.clone();
+        let out_grad = out.read().grad.clone();
+        let s_data = self.read().data.clone();
+
+        // derivative for ReLU operation
+        out.set_backward(Box::new(move || {
+            s_grad.add(if s_data.get() < 0.0 { 0.0 } else { out_grad.get() });
+        }));
+
+        out
+    }
+
+    pub fn get_data(&self) -> f64 {
+        self.read().data.get()
+    }
+
+    pub fn set_data(&self, val: f64) {
+        self.read().data.set(val);
+    }
+
+    pub fn get_grad(&self) -> f64 {
+        self.read().grad.get()
+    }
+
+    pub fn set_grad(&self, val: f64) {
+        self.read().grad.set(val);
+    }
+}
+
+impl Backend for SyncValue {
+    fn from_data(data: f64) -> Self {
+        SyncValue::new(data)
+    }
+
+    fn get_data(&self) -> f64 {
+        SyncValue::get_data(self)
+    }
+
+    fn set_data(&self, val: f64) {
+        SyncValue::set_data(self, val)
+    }
+
+    fn get_grad(&self) -> f64 {
+        SyncValue::get_grad(self)
+    }
+
+    fn set_grad(&self, val: f64) {
+        SyncValue::set_grad(self, val)
+    }
+
+    fn power(&self, exp: f64) -> Self {
+        SyncValue::power(self, exp)
+    }
+
+    fn relu(&self) -> Self {
+        SyncValue::relu(self)
+    }
+
+    fn backward(&self) {
+        SyncValue::backward(self)
+    }
+}
+
+impl PartialEq for SyncValue {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.core, &other.core)
+    }
+}
+
+impl Eq for SyncValue {}
+
+impl Hash for SyncValue {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        Arc::as_ptr(&self.core).hash(state);
+    }
+}
+
+impl ops::Add<&SyncValue> for &SyncValue {
+    type Output = SyncValue;
+
+    fn add(self, other: &SyncValue) -> Self::Output {
+        let out = SyncValue::new((
+            self.get_data() + other.get_data(),
+            Some(Operation::Addition),
+            Some(vec![self.clone(), other.clone()]),
+        ));
+
+        let s_grad = self.read().grad.clone();
+        let oth_grad = other.read().grad.clone();
+        let out_grad = out.read().grad.clone();
+
+        // derivative for add operation
+        out.set_backward(Box::new(move || {
+            s_grad.add(out_grad.get());
+            oth_grad.add(out_grad.get());
+        }));
+
+        out
+    }
+}
+
+impl ops::Add<&SyncValue> for SyncValue {
+    type Output = SyncValue;
+
+    fn add(self, other: &SyncValue) -> Self::Output {
+        &self + other
+    }
+}
+
+impl ops::Add<SyncValue> for &SyncValue {
+    type Output = SyncValue;
+
+    fn add(self, other: SyncValue) -> Self::Output {
+        self + &other
+    }
+}
+
+impl ops::Add<f64> for &SyncValue {
+    type Output = SyncValue;
+
+    fn add(self, other: f64) -> Self::Output {
+        self + SyncValue::new(other)
+    }
+}
+
+impl<'a> std::iter::Sum<&'a SyncValue> for SyncValue {
+    fn sum<I: Iterator<Item = &'a SyncValue>>(iter: I) -> Self {
+        iter.fold(
+            SyncValue::new(0.0),
+            |sum, el| sum + el,
+        )
+    }
+}
+
+impl ops::Mul<&SyncValue> for &SyncValue {
+    type Output = SyncValue;
+
+    fn mul(self, other: &SyncValue) -> Self::Output {
+        let out = SyncValue::new((
+            self.get_data() * other.get_data(),
+            Some(Operation::Multiplication),
+            Some(vec![self.clone(), other.clone()]),
+        ));
+
+        let s_grad = self.read().grad.clone();
+        let oth_grad = other.read().grad.clone();
+        let out_grad = out.read().grad.clone();
+
+        let s_data = self.read().data.clone();
+        let oth_data = other.read().data.clone();
+
+        // derivative for mul operation
+        out.set_backward(Box::new(move || {
+            s_grad.add(oth_data.get() * out_grad.get());
+            oth_grad.add(s_data.get() * out_grad.get());
+        }));
+
+        out
+    }
+}
+
+impl ops::Mul<&SyncValue> for SyncValue {
+    type Output = SyncValue;
+
+    fn mul(self, other: &SyncValue) -> Self::Output {
+        &self * other
+    }
+}
+
+impl ops::Mul<SyncValue> for &SyncValue {
+    type Output = SyncValue;
+
+    fn mul(self, other: SyncValue) -> Self::Output {
+        self * &other
+    }
+}
+
+impl ops::Neg for &SyncValue {
+    type Output = SyncValue;
+
+    fn neg(self) -> Self::Output {
+        self * &SyncValue::new(-1.0)
+    }
+}
+
+impl ops::Neg for SyncValue {
+    type Output = SyncValue;
+
+    fn neg(self) -> Self::Output {
+        self * &SyncValue::new(-1.0)
+    }
+}
+
+impl ops::Sub<&SyncValue> for &SyncValue {
+    type Output = SyncValue;
+
+    fn sub(self, other: &SyncValue) -> Self::Output {
+        self + &(-other)
+    }
+}
+
+impl ops::Sub<SyncValue> for SyncValue {
+    type Output = SyncValue;
+
+    fn sub(self, other: SyncValue) -> Self::Output {
+        self + &(-other)
+    }
+}
+
+impl ops::Div<f64> for SyncValue {
+    type Output = SyncValue;
+
+    fn div(self, other: f64) -> Self::Output {
+        self * &SyncValue::new(1.0/other)
+    }
+}
+
+impl ops::Div<&SyncValue> for &SyncValue {
+    type Output = SyncValue;
+
+    fn div(self, other: &SyncValue) -> Self::Output {
+        self * &other.power(-1.0)
+    }
+}
+
+impl Display for SyncValue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SYNC VALUE")
+            .field("DATA", &self.get_data())
+            .field("GRAD", &self.get_grad())
+            .field("OP", &self.read().op)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::thread;
+
+    #[test]
+    fn test_ops() {
+        let a = &SyncValue::new(1.0);
+        let b = &SyncValue::new(2.0);
+        let c = &(a + b);
+        let d = &(c * b);
+        let e = (d - a).relu();
+        let f = &e / b;
+
+        // testing operation
+        assert_eq!(f.get_data(), 2.5);
+
+        // testing derivative
+        f.backward();
+        assert_eq!(a.get_grad(), 0.5);
+        assert_eq!(b.get_grad(), 1.25);
+    }
+
+    #[test]
+    fn test_power() {
+        let a = &SyncValue::new(1.0);
+        let b = &SyncValue::new(2.0);
+        let c = &(a + b);
+        let d = c.power(2.0);
+
+        // testing operation
+        assert_eq!(d.get_data(), 9.0);
+
+        // testing derivative
+        d.backward();
+        assert_eq!(b.get_grad(), 6.0);
+    }
+
+    #[test]
+    fn test_across_threads() {
+        let a = SyncValue::new(3.0);
+        let b = SyncValue::new(4.0);
+
+        // graph built in one thread and backpropagated in another
+        let c = &a * &b;
+        thread::spawn(move || c.backward()).join().unwrap();
+        assert_eq!(a.get_grad(), 4.0);
+        assert_eq!(b.get_grad(), 3.0);
+
+        // shared leaves accumulating grads from concurrent backward passes
+        let handles: Vec<_> = (0..4)
+            .map(|_| {
+                let (a, b) = (a.clone(), b.clone());
+                thread::spawn(move || (&a + &b).backward())
+            })
+            .collect();
+        for h in handles {
+            h.join().unwrap();
+        }
+        assert_eq!(a.get_grad(), 8.0);
+        assert_eq!(b.get_grad(), 7.0);
+    }
+}
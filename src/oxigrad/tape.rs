@@ -0,0 +1,469 @@
+// tape based counterpart of the engine module: nodes live in a contiguous arena (one per thread)
+// indexed by integers, operations are recorded as data instead of boxed closures and, as a node
+// always comes after its inputs, backward is a reverse linear sweep with no topological sort
+use std::ops;
+use std::cell::RefCell;
+use std::marker::PhantomData;
+use std::fmt::Display;
+use super::engine::Backend;
+
+// operation that produced a node, with the indices of its inputs
+#[derive(Debug, Clone, Copy)]
+enum Op {
+    Leaf,
+    Addition(usize, usize),
+    Multiplication(usize, usize),
+    Power(usize, f64),
+    ReLU(usize),
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Node {
+    data: f64,
+    grad: f64,
+    op: Op,
+    // generation of the tape when the node was recorded
+    generation: u64,
+}
+
+// the generation changes whenever nodes are dropped, so that a value pointing to a dropped node is
+// told apart from the one recorded at the same index afterwards
+struct Tape {
+    nodes: Vec<Node>,
+    generation: u64,
+}
+
+thread_local! {
+    static TAPE: RefCell<Tape> = const { RefCell::new(Tape { nodes: Vec::new(), generation: 0 }) };
+}
+
+fn push(data: f64, op: Op) -> TapeValue {
+    TAPE.with(|t| {
+        let mut t = t.borrow_mut();
+        let generation = t.generation;
+        t.nodes.push(Node { data, grad: 0.0, op, generation });
+        TapeValue { index: t.nodes.len() - 1, generation, thread: PhantomData }
+    })
+}
+
+fn with_node<R>(value: &TapeValue, f: impl FnOnce(&mut Node) -> R) -> R {
+    TAPE.with(|t| match t.borrow_mut().nodes.get_mut(value.index) {
+        Some(node) if node.generation == value.generation => f(node),
+        _ => panic!("Node {} is no longer on the tape (it was truncated)", value.index),
+    })
+}
+
+// TAPE MANAGEMENT
+// number of nodes recorded on this thread's tape
+pub fn len() -> usize {
+    TAPE.with(|t| t.borrow().nodes.len())
+}
+
+// drops the nodes recorded after the first len ones (e.g. the graph of a training pass, keeping the
+// parameters recorded before it), values pointing to the dropped nodes panic if used afterwards
+pub fn truncate(len: usize) {
+    TAPE.with(|t| {
+        let mut t = t.borrow_mut();
+        if len < t.nodes.len() {
+            t.nodes.truncate(len);
+            t.generation += 1;
+        }
+    });
+}
+
+pub fn clear() {
+    truncate(0);
+}
+
+// truncates the tape back to its current length when the returned guard is dropped
+pub fn scope() -> TapeScope {
+    TapeScope { len: len() }
+}
+
+#[must_use = "the tape is truncated as soon as the guard is dropped"]
+pub struct TapeScope {
+    len: usize,
+}
+
+impl Drop for TapeScope {
+    fn drop(&mut self) {
+        truncate(self.len);
+    }
+}
+
+// TAPE VALUE IMPLEMENTATION
+// a handle to a node of this thread's tape, hence Copy but neither Send nor Sync (every thread has a tape
+// of its own, so on another thread it would point to an unrelated node)
+// same operators as Value, but backward differs: grads left on intermediate nodes by previous passes
+// aren't propagated again (see TapeValue::backward), so only the leaves' grads accumulate meaningfully
+/// ```compile_fail
+/// fn send<T: Send>(_: T) {}
+/// send(oxigrad::tape::TapeValue::new(1.0));
+/// ```
+/// ```compile_fail
+/// fn sync<T: Sync>(_: &T) {}
+/// sync(&oxigrad::tape::TapeValue::new(1.0));
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct TapeValue {
+    index: usize,
+    generation: u64,
+    thread: PhantomData<*const ()>,
+}
+
+impl TapeValue {
+    pub fn new(data: f64) -> Self {
+        push(data, Op::Leaf)
+    }
+
+    // position of the node on the tape
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    pub fn get_data(&self) -> f64 {
+        with_node(self, |n| n.data)
+    }
+
+    pub fn set_data(&self, val: f64) {
+        with_node(self, |n| n.data = val);
+    }
+
+    pub fn get_grad(&self) -> f64 {
+        with_node(self, |n| n.grad)
+    }
+
+    pub fn set_grad(&self, val: f64) {
+        with_node(self, |n| n.grad = val);
+    }
+
+    pub fn power(&self, exp: f64) -> Self {
+        push(self.get_data().powf(exp), Op::Power(self.index, exp))
+    }
+
+    pub fn relu(&self) -> Self {
+        let data = if self.get_data() >= 0.0 { self.get_data() } else { 0.0 };
+        push(data, Op::ReLU(self.index))
+    }
+
+    // the grads of the nodes this one depends on are accumulated, as with Value::backward, but the adjoints
+    // are computed apart first: grads left on the intermediate nodes by previous passes aren't propagated
+    // again (Value::backward would), so each pass adds exactly the derivatives of this node to the leaves
+    pub fn backward(&self) {
+        with_node(self, |_| ());
+        TAPE.with(|t| {
+            let mut t = t.borrow_mut();
+            let nodes = &mut t.nodes[..=self.index];
+
+            let mut adjoints = vec![0.0; nodes.len()];
+            adjoints[self.index] = 1.0;
+
+            for i in (0..nodes.len()).rev() {
+                let g = adjoints[i];
+                if g == 0.0 {
+                    continue;
+                }
+                match nodes[i].op {
+                    Op::Leaf => {},
+                    Op::Addition(a, b) => {
+                        adjoints[a] += g;
+                        adjoints[b] += g;
+                    },
+                    Op::Multiplication(a, b) => {
+                        adjoints[a] += nodes[b].data * g;
+                        adjoints[b] += nodes[a].data * g;
+                    },
+                    Op::Power(a, exp) => adjoints[a] += exp * nodes[a].data.powf(exp - 1.0) * g,
+                    Op::ReLU(a) => if nodes[a].data >= 0.0 { adjoints[a] += g },
+                }
+            }
+
+            for (n, a) in nodes.iter_mut().zip(adjoints) {
+                n.grad += a;
+            }
+            // a derivative of something (i.e. the starting node for the backward pass) w.r.t itself is 1
+            nodes[self.index].grad = 1.0;
+        });
+    }
+}
+
+impl Backend for TapeValue {
+    fn from_data(data: f64) -> Self {
+        TapeValue::new(data)
+    }
+
+    fn get_data(&self) -> f64 {
+        TapeValue::get_data(self)
+    }
+
+    fn set_data(&self, val: f64) {
+        TapeValue::set_data(self, val)
+    }
+
+    fn get_grad(&self) -> f64 {
+        TapeValue::get_grad(self)
+    }
+
+    fn set_grad(&self, val: f64) {
+        TapeValue::set_grad(self, val)
+    }
+
+    fn power(&self, exp: f64) -> Self {
+        TapeValue::power(self, exp)
+    }
+
+    fn relu(&self) -> Self {
+        TapeValue::relu(self)
+    }
+
+    fn backward(&self) {
+        TapeValue::backward(self)
+    }
+}
+
+// values are Copy, so the operations are implemented on them and the references just dereference
+impl ops::Add<TapeValue> for TapeValue {
+    type Output = TapeValue;
+
+    fn add(self, other: TapeValue) -> Self::Output {
+        push(self.get_data() + other.get_data(), Op::Addition(self.index, other.index))
+    }
+}
+
+impl ops::Add<&TapeValue> for &TapeValue {
+    type Output = TapeValue;
+
+    fn add(self, other: &TapeValue) -> Self::Output {
+        *self + *other
+    }
+}
+
+impl ops::Add<&TapeValue> for TapeValue {
+    type Output = TapeValue;
+
+    fn add(self, other: &TapeValue) -> Self::Output {
+        self + *other
+    }
+}
+
+impl ops::Add<TapeValue> for &TapeValue {
+    type Output = TapeValue;
+
+    fn add(self, other: TapeValue) -> Self::Output {
+        *self + other
+    }
+}
+
+impl ops::Add<f64> for TapeValue {
+    type Output = TapeValue;
+
+    fn add(self, other: f64) -> Self::Output {
+        self + TapeValue::new(other)
+    }
+}
+
+impl ops::Add<f64> for &TapeValue {
+    type Output = TapeValue;
+
+    fn add(self, other: f64) -> Self::Output {
+        *self + other
+    }
+}
+
+impl<'a> std::iter::Sum<&'a TapeValue> for TapeValue {
+    fn sum<I: Iterator<Item = &'a TapeValue>>(iter: I) -> Self {
+        iter.fold(
+            TapeValue::new(0.0),
+            |sum, el| sum + el,
+        )
+    }
+}
+
+impl ops::Mul<TapeValue> for TapeValue {
+    type Output = TapeValue;
+
+    fn mul(self, other: TapeValue) -> Self::Output {
+        push(self.get_data() * other.get_data(), Op::Multiplication(self.index, other.index))
+    }
+}
+
+impl ops::Mul<&TapeValue> for &TapeValue {
+    type Output = TapeValue;
+
+    fn mul(self, other: &TapeValue) -> Self::Output {
+        *self * *other
+    }
+}
+
+impl ops::Mul<&TapeValue> for TapeValue {
+    type Output = TapeValue;
+
+    fn mul(self, other: &TapeValue) -> Self::Output {
+        self * *other
+    }
+}
+
+impl ops::Mul<TapeValue> for &TapeValue {
+    type Output = TapeValue;
+
+    fn mul(self, other: TapeValue) -> Self::Output {
+        *self * other
+    }
+}
+
+impl ops::Neg for TapeValue {
+    type Output = TapeValue;
+
+    fn neg(self) -> Self::Output {
+        self * TapeValue::new(-1.0)
+    }
+}
+
+impl ops::Neg for &TapeValue {
+    type Output = TapeValue;
+
+    fn neg(self) -> Self::Output {
+        -*self
+    }
+}
+
+impl ops::Sub<TapeValue> for TapeValue {
+    type Output = TapeValue;
+
+    fn sub(self, other: TapeValue) -> Self::Output {
+        self + -other
+    }
+}
+
+impl ops::Sub<&TapeValue> for &TapeValue {
+    type Output = TapeValue;
+
+    fn sub(self, other: &TapeValue) -> Self::Output {
+        *self - *other
+    }
+}
+
+impl ops::Div<TapeValue> for TapeValue {
+    type Output = TapeValue;
+
+    fn div(self, other: TapeValue) -> Self::Output {
+        self * other.power(-1.0)
+    }
+}
+
+impl ops::Div<&TapeValue> for &TapeValue {
+    type Output = TapeValue;
+
+    fn div(self, other: &TapeValue) -> Self::Output {
+        *self / *other
+    }
+}
+
+impl ops::Div<f64> for TapeValue {
+    type Output = TapeValue;
+
+    fn div(self, other: f64) -> Self::Output {
+        self * TapeValue::new(1.0/other)
+    }
+}
+
+impl Display for TapeValue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TAPE VALUE")
+            .field("INDEX", &self.index)
+            .field("DATA", &self.get_data())
+            .field("GRAD", &self.get_grad())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use super::super::engine::Value;
+    use super::super::nn::{Base, Model, TapeModel};
+
+    #[test]
+    fn test_ops_match_engine() {
+        let (a, b, c) = (TapeValue::new(2.0), TapeValue::new(-3.0), TapeValue::new(0.5));
+        let out = (a * b + c / a).relu() + ((a - c).power(3.0) + 1.0);
+        let out = out + [a, b, c].iter().sum::<TapeValue>() / 4.0;
+        out.backward();
+
+        let (va, vb, vc) = (Value::new(2.0), Value::new(-3.0), Value::new(0.5));
+        let v_out = &(&(&va * &vb) + &(&vc / &va)).relu() + &(&(&va - &vc).power(3.0) + 1.0);
+        let v_out = &v_out + &([va.clone(), vb.clone(), vc.clone()].iter().sum::<Value>() / 4.0);
+        v_out.backward();
+
+        assert_eq!(out.get_data(), v_out.get_data());
+        assert_eq!(
+            (a.get_grad(), b.get_grad(), c.get_grad()),
+            (va.get_grad(), vb.get_grad(), vc.get_grad()),
+        );
+    }
+
+    #[test]
+    fn test_backward_accumulates() {
+        let a = TapeValue::new(3.0);
+        let unrelated = a * a;
+        unrelated.backward();
+        assert_eq!(a.get_grad(), 6.0);
+
+        // the stale grad of the unrelated node isn't propagated again
+        let b = a + a;
+        b.backward();
+        assert_eq!(a.get_grad(), 8.0);
+    }
+
+    #[test]
+    fn test_scope() {
+        let a = TapeValue::new(1.0);
+        let before = len();
+        {
+            let _scope = scope();
+            let b = a * a;
+            assert_eq!(b.index(), before);
+            assert_eq!(len(), before + 1);
+        }
+        assert_eq!(len(), before);
+    }
+
+    #[test]
+    fn test_stale_values() {
+        let a = TapeValue::new(1.0);
+        let stale = {
+            let _scope = scope();
+            a * a
+        };
+        // a new node recorded at the same index
+        let b = a + a;
+        assert_eq!(b.index(), stale.index());
+        assert_eq!(b.get_data(), 2.0);
+
+        assert!(std::panic::catch_unwind(|| stale.get_data()).is_err());
+        assert!(std::panic::catch_unwind(|| stale.backward()).is_err());
+        assert_eq!(a.get_data(), 1.0);
+    }
+
+    #[test]
+    fn test_model() {
+        let m = Model::new(2, &[4, 4, 1]);
+        let t = TapeModel::with_backend(2, &[4, 4, 1]);
+        for (p, q) in m.params().iter().zip(t.params().iter()) {
+            q.set_data(p.get_data());
+        }
+
+        let params = len();
+        let _scope = scope();
+        let v_loss = (&m.forward(&[0.5, -1.0]) - &Value::new(1.0)).power(2.0);
+        let t_loss = (t.forward(&[0.5, -1.0]) - TapeValue::new(1.0)).power(2.0);
+        v_loss.backward();
+        t_loss.backward();
+
+        assert!(len() > params);
+        assert_eq!(v_loss.get_data(), t_loss.get_data());
+        for (p, q) in m.params().iter().zip(t.params().iter()) {
+            assert_eq!(p.get_grad(), q.get_grad());
+        }
+    }
+}
@@ -0,0 +1,339 @@
+use super::engine::{no_grad, Value};
+use super::compile::CompiledGraph;
+use super::nn::{Base, Model};
+use super::optim::Optimizer;
+use super::callbacks::{Callback, CallbackList, Control, Logs};
+use super::history::{History, Record};
+use super::utils::{mse, svm_maxmargin, l2, accuracy};
+use super::error::OxiError;
+
+// loss between a prediction and its expected value
+pub type LossFn = fn(&Value, f64) -> Value;
+
+// loss function by name (as used on the command line), None if the name is unknown
+pub fn loss_by_name(name: &str) -> Option<LossFn> {
+    match name {
+        "mse" => Some(mse),
+        "hinge" => Some(svm_maxmargin),
+        _ => None,
+    }
+}
+
+pub const LOSSES: [&str; 2] = ["mse", "hinge"];
+
+// learning rate for a pass, given the pass and the number of epochs (e.g. utils::alpha)
+pub type Schedule = Box<dyn Fn(i32, i32) -> f64>;
+
+// TRAINING OPTIONS IMPLEMENTATION
+pub struct TrainOptions {
+    pub epochs: usize,
+    pub loss: LossFn,
+    pub l2_lambda: f64,
+    // the optimizer's learning rate is kept if None
+    pub schedule: Option<Schedule>,
+    // print a line with the metrics of each pass
+    pub verbose: bool,
+}
+
+impl Default for TrainOptions {
+    fn default() -> Self {
+        TrainOptions {
+            epochs: 50,
+            loss: mse,
+            l2_lambda: 0.0,
+            schedule: None,
+            verbose: true,
+        }
+    }
+}
+
+// metrics of a model on a set of samples
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Evaluation {
+    pub loss: f64,
+    pub accuracy: f64,
+}
+
+pub fn evaluate(model: &Model, data: &[Vec<f64>], labels: &[f64], loss: LossFn) -> Evaluation {
+    let _guard = no_grad();
+    let preds: Vec<Value> = data.iter().map(|x| model.forward(x)).collect();
+
+    Evaluation {
+        loss: preds.iter().zip(labels).map(|(p, l)| loss(p, *l).get_data()).sum::<f64>() / labels.len() as f64,
+        accuracy: accuracy(&preds, labels),
+    }
+}
+
+// TRAINING LOOP
+// full batch training (loss averaged over the whole training set plus L2 regularization),
+// reporting "loss" and, when there's a validation set, "val_loss" and "val_accuracy" to the callbacks
+// each epoch is a single batch (number 0, the whole training set) whose end gets "loss" only, a stop
+// asked there taking effect once the epoch ends
+// errors are anomalies (see engine::detect_anomaly), graphs that can't be backpropagated and callbacks
+// aborting the training, all ending it right away (i.e. without on_train_end)
+// the graph is compiled once and replayed at every pass, so leaves created by the loss function (e.g. with
+// Value::detach) are constants holding their data of the first pass, while the parameters are read as
+// they are at each pass; graphs that can't be replayed (see OxiError::NotReplayable) are rebuilt at every
+// pass instead, while the other errors found when compiling end the training before it starts
+pub fn fit(
+    model: &Model,
+    optimizer: &mut dyn Optimizer,
+    train: (&[Vec<f64>], &[f64]),
+    validation: Option<(&[Vec<f64>], &[f64])>,
+    options: &TrainOptions,
+    callbacks: &mut CallbackList,
+) -> Result<History, OxiError> {
+    let (train_x, train_y) = train;
+    let epochs = options.epochs as i32;
+    let mut history = History::new();
+
+    // predictions, loss, L2 regularization and total loss
+    let build = || {
+        let preds: Vec<Value> = train_x.iter().map(|x| model.forward(x)).collect();
+        let losses: Vec<Value> = preds.iter()
+            .zip(train_y)
+            .map(|(p, l)| (options.loss)(p, *l))
+            .collect();
+        let loss = losses.iter().sum::<Value>() / losses.len() as f64;
+        let reg = l2(&model.params(), Some(&Value::new(options.l2_lambda)));
+        let tot_loss = &loss + &reg;
+        (preds, loss, reg, tot_loss)
+    };
+
+    // the graph is the same at every pass (only the parameters change), so it's built once and replayed
+    let (mut preds, mut loss, mut reg, mut tot_loss) = build();
+    let graph = match CompiledGraph::compile(&tot_loss, &[]) {
+        Ok(graph) => Some(graph),
+        Err(OxiError::NotReplayable { .. }) => None,
+        Err(e) => return Err(e),
+    };
+
+    callbacks.on_train_start(model);
+    for pass in 0..epochs {
+        if let Some(schedule) = &options.schedule {
+            optimizer.set_lr(schedule(pass, epochs));
+        }
+        match callbacks.on_epoch_start(pass as usize, model) {
+            Control::Continue => {},
+            Control::Stop => break,
+            Control::Abort(e) => return Err(OxiError::Aborted(e)),
+        }
+
+        match callbacks.on_batch_start(0, model) {
+            Control::Continue => {},
+            Control::Stop => break,
+            Control::Abort(e) => return Err(OxiError::Aborted(e)),
+        }
+
+        // prepping
+        model.zero_grad();
+
+        // forward and backward pass
+        match &graph {
+            Some(graph) => {
+                graph.forward(&[])?;
+                graph.backward()?;
+            },
+            None => {
+                if pass > 0 {
+                    (preds, loss, reg, tot_loss) = build();
+                }
+                tot_loss.try_backward()?;
+            },
+        }
+        optimizer.step(&model.params());
+
+        let stop = match callbacks.on_batch_end(0, &Logs::from([("loss".to_string(), loss.get_data())]), model) {
+            Control::Continue => false,
+            Control::Stop => true,
+            Control::Abort(e) => return Err(OxiError::Aborted(e)),
+        };
+
+        let mut record = Record::new(pass as usize, optimizer.lr(), &preds, &reg, &loss, &tot_loss);
+        let mut logs = Logs::from([("loss".to_string(), loss.get_data())]);
+        let mut line = format!(
+            "pass={}, alpha={:.3}, reg={:.6}, loss={:.6}, tot_loss={:.6}",
+            pass,
+            optimizer.lr(),
+            reg.get_data(),
+            loss.get_data(),
+            tot_loss.get_data(),
+        );
+        if let Some((val_x, val_y)) = validation {
+            let val = evaluate(model, val_x, val_y, options.loss);
+            record = record.metric("val_loss", val.loss).metric("val_accuracy", val.accuracy);
+            logs.insert("val_loss".to_string(), val.loss);
+            logs.insert("val_accuracy".to_string(), val.accuracy);
+            line.push_str(&format!(", val_loss={:.6}, val_accuracy={:.0}%", val.loss, val.accuracy * 100.0));
+        }
+        if options.verbose {
+            println!("{}", line);
+        }
+        history.push(record);
+
+        match callbacks.on_epoch_end(pass as usize, &logs, model) {
+            Control::Continue if !stop => {},
+            Control::Continue | Control::Stop => {
+                if options.verbose {
+                    println!("==> Training stopped early at pass={}", pass);
+                }
+                break;
+            },
+            Control::Abort(e) => return Err(OxiError::Aborted(e)),
+        }
+    }
+    callbacks.on_train_end(model);
+
+    Ok(history)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use super::super::optim::Sgd;
+    use super::super::data::{INP_DATASET, LBLS_DATASET};
+    use super::super::callbacks::EarlyStopping;
+    use super::super::utils::alpha;
+    use super::super::engine::{detect_anomaly, Operation};
+    use super::super::error::Pass;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    fn data() -> (Vec<Vec<f64>>, Vec<f64>) {
+        (INP_DATASET.iter().map(|r| r.to_vec()).collect(), LBLS_DATASET.to_vec())
+    }
+
+    #[test]
+    fn test_fit() {
+        let (x, y) = data();
+        let m = Model::new(2, &[8, 1]);
+        let options = TrainOptions { epochs: 20, verbose: false, ..TrainOptions::default() };
+        let history = fit(&m, &mut Sgd::new(0.05), (&x, &y), Some((&x, &y)), &options, &mut CallbackList::default()).unwrap();
+        let losses = history.series("loss");
+
+        assert_eq!(history.len(), 20);
+        assert!(losses[19] < losses[0]);
+        assert_eq!(history.series("val_loss").len(), 20);
+    }
+
+    #[test]
+    fn test_fit_replays_graph() {
+        // same parameters updated through the replayed graph and through a graph rebuilt every pass
+        let (x, y) = data();
+        let m = Model::new(2, &[4, 1]);
+        let rebuilt = Model::new(2, &[4, 1]);
+        for (p, q) in m.params().iter().zip(rebuilt.params().iter()) {
+            q.set_data(p.get_data());
+        }
+        let options = TrainOptions { epochs: 3, l2_lambda: 0.01, verbose: false, ..TrainOptions::default() };
+        fit(&m, &mut Sgd::new(0.1), (&x, &y), None, &options, &mut CallbackList::default()).unwrap();
+
+        let mut sgd = Sgd::new(0.1);
+        for _ in 0..3 {
+            rebuilt.zero_grad();
+            let losses: Vec<Value> = x.iter().zip(y.iter()).map(|(x, y)| mse(&rebuilt.forward(x), *y)).collect();
+            let loss = losses.iter().sum::<Value>() / losses.len() as f64;
+            (&loss + &l2(&rebuilt.params(), Some(&Value::new(0.01)))).backward();
+            sgd.step(&rebuilt.params());
+        }
+
+        for (p, q) in m.params().iter().zip(rebuilt.params().iter()) {
+            assert!((p.get_data() - q.get_data()).abs() < 1e-12);
+        }
+    }
+
+    #[test]
+    fn test_fit_rebuilt_graph() {
+        // a node built by hand can't be compiled: the graph is rebuilt at every pass and backward's error returned
+        fn by_hand(p: &Value, _: f64) -> Value {
+            Value::new((p.get_data(), Some(Operation::Division), Some(vec![p.clone()])))
+        }
+
+        let (x, y) = data();
+        let m = Model::new(2, &[4, 1]);
+        let options = TrainOptions { epochs: 3, loss: by_hand, verbose: false, ..TrainOptions::default() };
+        let result = fit(&m, &mut Sgd::new(0.1), (&x, &y), None, &options, &mut CallbackList::default());
+
+        assert!(matches!(result, Err(OxiError::MissingBackward { op: Some(Operation::Division) })));
+    }
+
+    #[test]
+    fn test_fit_compile_errors() {
+        // other errors than a graph that can't be replayed are returned, e.g. an anomaly found when compiling
+        fn infinite(p: &Value, _: f64) -> Value {
+            (p - p).power(-1.0)
+        }
+
+        let (x, y) = data();
+        let m = Model::new(2, &[4, 1]);
+        let options = TrainOptions { epochs: 3, loss: infinite, verbose: false, ..TrainOptions::default() };
+        let _guard = detect_anomaly();
+        let result = fit(&m, &mut Sgd::new(0.1), (&x, &y), None, &options, &mut CallbackList::default());
+
+        assert!(matches!(result, Err(OxiError::Anomaly(a)) if a.pass == Pass::Forward && a.op == "Power"));
+    }
+
+    #[test]
+    fn test_fit_schedule_and_stop() {
+        let (x, y) = data();
+        let m = Model::new(2, &[4, 1]);
+        let options = TrainOptions {
+            epochs: 10,
+            schedule: Some(Box::new(alpha)),
+            verbose: false,
+            ..TrainOptions::default()
+        };
+        let mut callbacks = CallbackList::new(vec![Box::new(EarlyStopping::new("loss").patience(0).min_delta(f64::INFINITY))]);
+        let history = fit(&m, &mut Sgd::new(1.0), (&x, &y), None, &options, &mut callbacks).unwrap();
+
+        // the first pass sets the best loss, the second one can't improve by an infinite delta
+        assert_eq!(history.len(), 2);
+        assert_eq!(history.series("alpha"), vec![alpha(0, 10), alpha(1, 10)]);
+        assert!(history.series("val_loss").is_empty());
+    }
+
+    #[test]
+    fn test_fit_batch_hooks() {
+        // one batch per epoch, a stop asked at its end taking effect once the epoch ends
+        struct Recorder(Rc<RefCell<Vec<String>>>);
+
+        impl Callback for Recorder {
+            fn on_epoch_end(&mut self, epoch: usize, _logs: &Logs, _model: &dyn Base) -> Control {
+                self.0.borrow_mut().push(format!("epoch_end {}", epoch));
+                Control::Continue
+            }
+
+            fn on_batch_start(&mut self, batch: usize, _model: &dyn Base) -> Control {
+                self.0.borrow_mut().push(format!("batch_start {}", batch));
+                Control::Continue
+            }
+
+            fn on_batch_end(&mut self, batch: usize, logs: &Logs, _model: &dyn Base) -> Control {
+                let mut events = self.0.borrow_mut();
+                events.push(format!("batch_end {} {}", batch, logs.len()));
+                if events.len() > 3 { Control::Stop } else { Control::Continue }
+            }
+        }
+
+        let (x, y) = data();
+        let m = Model::new(2, &[4, 1]);
+        let options = TrainOptions { epochs: 10, verbose: false, ..TrainOptions::default() };
+        let events = Rc::new(RefCell::new(Vec::new()));
+        let mut callbacks = CallbackList::new(vec![Box::new(Recorder(events.clone()))]);
+        let history = fit(&m, &mut Sgd::new(0.1), (&x, &y), Some((&x, &y)), &options, &mut callbacks).unwrap();
+
+        assert_eq!(history.len(), 2);
+        assert_eq!(*events.borrow(), vec![
+            "batch_start 0", "batch_end 0 1", "epoch_end 0",
+            "batch_start 0", "batch_end 0 1", "epoch_end 1",
+        ]);
+    }
+
+    #[test]
+    fn test_loss_by_name() {
+        let p = Value::new(0.5);
+        assert_eq!(loss_by_name("mse").unwrap()(&p, 1.0).get_data(), 0.25);
+        assert_eq!(loss_by_name("hinge").unwrap()(&p, 1.0).get_data(), 0.5);
+        assert!(loss_by_name("mae").is_none());
+    }
+}
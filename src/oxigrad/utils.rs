@@ -1,5 +1,8 @@
 use crate::oxigrad::engine::Value;
 use crate::oxigrad::engine::Operation;
+use crate::oxigrad::data::seeded_rng;
+use crate::oxigrad::error::OxiError;
+use rand::{Rng, StdRng};
 
 // dynamic learning rate function dependent on # of cycle iterations (from 0 to a maximum of 500 passes)
 // mind that the hyperparameters chosen here could not work well for some NN architectures
@@ -25,7 +28,7 @@ pub fn svm_maxmargin(predicted: &Value, exp: f64) -> Value {
     (&(-expected * predicted) + 1.0).relu()
 }
 
-pub fn l2(model_params: &Vec<Value>, lambda: Option<&Value>) -> Value {
+pub fn l2(model_params: &[Value], lambda: Option<&Value>) -> Value {
     let squared: Vec<Value> = model_params
         .iter()
         .map(|v| v.power(2.0))
@@ -39,17 +42,21 @@ pub fn l2(model_params: &Vec<Value>, lambda: Option<&Value>) -> Value {
     reg
 }
 
+// data groups and the respective label groups
+pub type Groups<T> = (Vec<Vec<T>>, Vec<Vec<f64>>);
+
 // split data into equal sized groups
-pub fn group(data: Vec<[f64; 2]>, labels: Vec<f64>, k: Option<usize>) -> (Vec<Vec<[f64; 2]>>, Vec<Vec<f64>>) {
+// (rows can be of any kind, e.g. the fixed size [f64; 2] or the Vec<f64> of a Dataset)
+pub fn group<T: Clone>(data: Vec<T>, labels: Vec<f64>, k: Option<usize>) -> Groups<T> {
     // if no size is given then keep the data undivided (i.e. with the whole length)
     let group_size = k.unwrap_or(data.len());
-    let size = (data.len() / group_size) as usize;
+    let size = data.len() / group_size;
     let mut data_groups = Vec::new();
     let mut labels_groups: Vec<Vec<f64>> = Vec::new();
     let mut start = 0;
 
     for _ in 0..group_size {
-        data_groups.push(Vec::from(data[start..start+size].to_vec()));
+        data_groups.push(data[start..start+size].to_vec());
         labels_groups.push(Vec::from_iter(labels[start..start+size].iter().cloned()));
         start += size;
     }
@@ -57,7 +64,124 @@ pub fn group(data: Vec<[f64; 2]>, labels: Vec<f64>, k: Option<usize>) -> (Vec<Ve
     (data_groups, labels_groups)
 }
 
+// same as group but returning an error when data and labels differ in length
+// or the data can't be split into k non-empty groups
+pub fn try_group<T: Clone>(data: Vec<T>, labels: Vec<f64>, k: Option<usize>) -> Result<Groups<T>, OxiError> {
+    if data.len() != labels.len() {
+        return Err(OxiError::LengthMismatch { data: data.len(), labels: labels.len() });
+    }
+    // same default as group, i.e. one sample per group
+    let groups = k.unwrap_or(data.len());
+    if groups == 0 || groups > data.len() {
+        return Err(OxiError::InvalidK { k: groups, samples: data.len() });
+    }
+
+    Ok(group(data, labels, k))
+}
+
+// fraction of predictions on the same side of zero as the respective labels (labels being -1.0/1.0)
+pub fn accuracy(preds: &[Value], labels: &[f64]) -> f64 {
+    let directions = preds.iter()
+        .zip(labels)
+        .map(|(p, e)|
+            if (p.get_data()>0.0) == (*e>0.0) {
+                1.0
+            } else {
+                0.0
+            }
+        ).collect::<Vec<f64>>();
+
+    directions.iter().sum::<f64>() / directions.len() as f64
+}
+
+// data and labels belonging to the same subset
+pub type Subset<T> = (Vec<T>, Vec<f64>);
+
+pub struct Split<T> {
+    pub train: Subset<T>,
+    pub validation: Subset<T>,
+    pub test: Subset<T>,
+}
+
+// shuffled and seeded split into training, validation and test sets (training gets what's left from the other two)
+// in stratified mode each set keeps the same proportion of every label as the whole data
+pub fn train_val_test_split<T: Clone>(
+    data: &[T],
+    labels: &[f64],
+    val_ratio: f64,
+    test_ratio: f64,
+    seed: u64,
+    stratified: bool,
+) -> Result<Split<T>, OxiError> {
+    if data.len() != labels.len() {
+        return Err(OxiError::LengthMismatch { data: data.len(), labels: labels.len() });
+    }
+    // written so that NaNs fail too
+    if !(val_ratio >= 0.0 && test_ratio >= 0.0 && val_ratio + test_ratio <= 1.0) {
+        return Err(OxiError::InvalidRatios { validation: val_ratio, test: test_ratio });
+    }
+
+    let mut rng = seeded_rng(seed);
+
+    // samples are split independently for each label in stratified mode, all together otherwise
+    let mut strata: Vec<Vec<usize>> = Vec::new();
+    if stratified {
+        let mut seen: Vec<f64> = Vec::new();
+        for (i, l) in labels.iter().enumerate() {
+            match seen.iter().position(|s| s == l) {
+                Some(pos) => strata[pos].push(i),
+                None => {
+                    seen.push(*l);
+                    strata.push(vec![i]);
+                }
+            }
+        }
+    } else {
+        strata.push((0..labels.len()).collect());
+    }
+
+    let (mut train, mut validation, mut test) = (Vec::new(), Vec::new(), Vec::new());
+    for stratum in strata.iter_mut() {
+        rng.shuffle(stratum);
+
+        let n_test = (stratum.len() as f64 * test_ratio).round() as usize;
+        let n_val = ((stratum.len() as f64 * val_ratio).round() as usize).min(stratum.len() - n_test);
+        test.extend_from_slice(&stratum[..n_test]);
+        validation.extend_from_slice(&stratum[n_test..n_test+n_val]);
+        train.extend_from_slice(&stratum[n_test+n_val..]);
+    }
+
+    // strata are concatenated, so mix them up again
+    let subset = |indexes: &mut Vec<usize>, rng: &mut StdRng| -> Subset<T> {
+        rng.shuffle(indexes);
+        (
+            indexes.iter().map(|i| data[*i].clone()).collect(),
+            indexes.iter().map(|i| labels[*i]).collect(),
+        )
+    };
+
+    Ok(Split {
+        train: subset(&mut train, &mut rng),
+        validation: subset(&mut validation, &mut rng),
+        test: subset(&mut test, &mut rng),
+    })
+}
+
+// same as train_val_test_split but without the validation set
+pub fn train_test_split<T: Clone>(
+    data: &[T],
+    labels: &[f64],
+    test_ratio: f64,
+    seed: u64,
+    stratified: bool,
+) -> Result<(Subset<T>, Subset<T>), OxiError> {
+    let split = train_val_test_split(data, labels, 0.0, test_ratio, seed, stratified)?;
+    Ok((split.train, split.test))
+}
+
 #[cfg(test)]
+// the tests predating the lints are kept as written
+#[allow(clippy::toplevel_ref_arg, clippy::bool_assert_comparison)]
 mod test {
     use super::*;
 
@@ -87,6 +211,75 @@ mod test {
         assert_eq!((alpha(314, 500) * 10_000_f64).round() / 10_000_f64, 0.0174);
     }
 
+    #[test]
+    fn test_accuracy() {
+        let preds = [Value::new(0.3), Value::new(-1.2), Value::new(2.0), Value::new(-0.1)];
+        assert_eq!(accuracy(&preds, &[1.0, -1.0, -1.0, -1.0]), 0.75);
+    }
+
+    #[test]
+    fn test_train_val_test_split() {
+        use crate::oxigrad::data::{INP_DATASET, LBLS_DATASET};
+
+        let split = train_val_test_split(&INP_DATASET, &LBLS_DATASET, 0.2, 0.1, 42, false).unwrap();
+        assert_eq!(split.train.0.len(), 70);
+        assert_eq!(split.validation.0.len(), 20);
+        assert_eq!(split.test.0.len(), 10);
+
+        // every sample ends up in exactly one set, still paired with its own label
+        let mut all: Vec<([f64; 2], f64)> = Vec::new();
+        for (d, l) in [&split.train, &split.validation, &split.test] {
+            all.extend(d.iter().cloned().zip(l.iter().cloned()));
+        }
+        assert_eq!(all.len(), 100);
+        assert!(INP_DATASET.iter().zip(LBLS_DATASET.iter()).all(|(d, l)| all.contains(&(*d, *l))));
+
+        // seeded
+        let again = train_val_test_split(&INP_DATASET, &LBLS_DATASET, 0.2, 0.1, 42, false).unwrap();
+        assert_eq!(split.test, again.test);
+
+        assert!(matches!(
+            train_val_test_split(&INP_DATASET, &LBLS_DATASET, 0.6, 0.5, 42, false),
+            Err(OxiError::InvalidRatios { .. }),
+        ));
+        assert!(matches!(
+            train_val_test_split(&INP_DATASET, &LBLS_DATASET, f64::NAN, 0.1, 42, false),
+            Err(OxiError::InvalidRatios { .. }),
+        ));
+        assert!(matches!(
+            train_test_split(&INP_DATASET, &LBLS_DATASET[1..], 0.1, 42, false),
+            Err(OxiError::LengthMismatch { data: 100, labels: 99 }),
+        ));
+    }
+
+    #[test]
+    fn test_stratified_split() {
+        // 80 negatives and 20 positives, with arbitrary width rows
+        let data: Vec<Vec<f64>> = (0..100).map(|i| vec![i as f64; 3]).collect();
+        let labels: Vec<f64> = (0..100).map(|i| if i < 80 { -1.0 } else { 1.0 }).collect();
+
+        let split = train_val_test_split(&data, &labels, 0.25, 0.25, 7, true).unwrap();
+        for (_, l) in [&split.train, &split.validation, &split.test] {
+            let positives = l.iter().filter(|l| **l > 0.0).count();
+            assert_eq!(positives * 5, l.len());
+        }
+
+        let (train, test) = train_test_split(&data, &labels, 0.3, 7, true).unwrap();
+        assert_eq!(train.0.len(), 70);
+        assert_eq!(test.1.iter().filter(|l| **l > 0.0).count(), 6);
+    }
+
+    #[test]
+    fn test_try_group() {
+        let data = vec![1.0, 2.0, 3.0, 4.0];
+
+        assert_eq!(try_group(data.clone(), vec![1.0; 4], Some(2)).unwrap().0, vec![vec![1.0, 2.0], vec![3.0, 4.0]]);
+        assert!(matches!(try_group(data.clone(), vec![1.0; 4], Some(5)), Err(OxiError::InvalidK { k: 5, samples: 4 })));
+        assert!(matches!(try_group(data.clone(), vec![1.0; 4], Some(0)), Err(OxiError::InvalidK { k: 0, .. })));
+        assert!(matches!(try_group(data, vec![1.0; 3], None), Err(OxiError::LengthMismatch { data: 4, labels: 3 })));
+        assert!(try_group(Vec::<f64>::new(), Vec::new(), None).is_err());
+    }
+
     #[test]
     fn test_groups() {
         // dummy data
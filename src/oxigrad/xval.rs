@@ -1,27 +1,31 @@
 use std::collections::HashMap;
 use std::fmt::Display;
-use super::engine::Value;
-use super::nn::Model;
-use super::nn::Base;
-use super::utils::{l2, group};
+use super::engine::{no_grad, Value};
+use super::nn::{Model, Activation};
+use super::utils::{try_group, accuracy};
+use super::optim::{Optimizer, Sgd};
+use super::train::{fit, Schedule, TrainOptions};
+use super::callbacks::CallbackList;
+use super::error::OxiError;
 
 
 // RANGE IMPLEMENTATION WITH FLOATING VALUES
+// from start to end (both included), empty if step isn't positive
 #[derive(Clone, Copy)]
-pub(crate) struct FloatingRange {
+pub struct FloatingRange {
     start: f64,
     end: f64,
     step: f64,
-    current: f64,
+    index: usize,
 }
 
 impl FloatingRange {
-    pub(crate) fn new(start: f64, end: f64, step: f64) -> Self {
+    pub fn new(start: f64, end: f64, step: f64) -> Self {
         FloatingRange {
             start,
             end,
             step,
-            current: start,
+            index: 0,
         }
     }
 }
@@ -30,9 +34,15 @@ impl Iterator for FloatingRange {
     type Item = f64;
 
     fn next(&mut self) -> Option<Self::Item> {
-        if self.current <= self.end {
-            self.current = self.current + self.step;
-            Some(self.current)
+        if self.step.is_nan() || self.step <= 0.0 {
+            return None;
+        }
+
+        // computed from the index so that rounding errors don't add up (nor drop the end)
+        let current = self.start + self.step * self.index as f64;
+        if current <= self.end + self.step * 1e-9 {
+            self.index += 1;
+            Some(current)
         } else {
             None
         }
@@ -41,51 +51,87 @@ impl Iterator for FloatingRange {
 
 
 // CROSS VALIDATION IMPLEMENTATION AS struct
-pub(crate) struct XVal<'a> {
+// each fold trains a newly initialized model with train::fit on the other folds
+pub struct XVal<'a> {
     model: Option<Model>,
-    model_arch: &'a Vec<usize>,
+    model_arch: &'a [usize],
+    activations: Vec<Activation>,
+    input_size: usize,
     k: usize,
     // alpha: f64,
     alpha: fn(i32, i32) -> f64,
-    loss_fn: fn(&Value,f64) -> Value,
-    values: Vec<Vec<[f64; 2]>>,
+    // training of each fold, l2_lambda being the hyperparameter searched
+    options: TrainOptions,
+    // a new optimizer for each fold, SGD with the first alpha if None
+    optimizer: Option<Box<dyn Fn() -> Box<dyn Optimizer>>>,
+    values: Vec<Vec<Vec<f64>>>,
     labels: Vec<Vec<f64>>,
     hyper_range: FloatingRange,
     cv_scores: HashMap<String, Vec<f64>>,
 }
 
 impl<'a> XVal<'a> {
-    pub(crate) fn new(
-        data_ds: Vec<[f64; 2]>,
+    pub fn new(
+        data_ds: Vec<Vec<f64>>,
         labels_ds: Vec<f64>,
-        model_arch: &'a Vec<usize>,
+        model_arch: &'a [usize],
         hyper_range: FloatingRange,
         // alpha: f64,
         alpha: fn(i32, i32) -> f64,
         loss_fn: fn(&Value,f64) -> Value,
         k: usize,
-    ) -> Self {
-        let (values, labels) = group(data_ds, labels_ds, Some(k));
+    ) -> Result<Self, OxiError> {
+        let input_size = data_ds.first().map_or(0, |r| r.len());
+        let (values, labels) = try_group(data_ds, labels_ds, Some(k))?;
         
-        XVal {
+        Ok(XVal {
             model: None,
             model_arch,
-            k: 10,
+            activations: Activation::defaults(model_arch.len()),
+            input_size,
+            k,
             alpha,
-            loss_fn,
+            options: TrainOptions {
+                loss: loss_fn,
+                schedule: Some(Box::new(alpha)),
+                verbose: false,
+                ..TrainOptions::default()
+            },
+            optimizer: None,
             values,
             labels,
             hyper_range,
             cv_scores: HashMap::new(),
-        }
+        })
+    }
+
+    // the folds are trained like the final model should be (e.g. the same settings of an experiment)
+    pub fn activations(mut self, activations: &[Activation]) -> Self {
+        self.activations = activations.to_vec();
+        self
+    }
+
+    pub fn optimizer(mut self, optimizer: impl Fn() -> Box<dyn Optimizer> + 'static) -> Self {
+        self.optimizer = Some(Box::new(optimizer));
+        self
+    }
+
+    // learning rate of each pass, the optimizer's one for the whole training if None
+    pub fn schedule(mut self, schedule: Option<Schedule>) -> Self {
+        self.options.schedule = schedule;
+        self
+    }
+
+    pub fn epochs(mut self, epochs: usize) -> Self {
+        self.options.epochs = epochs;
+        self
     }
 
-    pub(crate) fn search_best_hyperpar(&mut self) -> f64 {
+    // errors are the ones of building (e.g. activations not matching the architecture) and training (see train::fit) a fold
+    pub fn search_best_hyperpar(&mut self) -> Result<f64, OxiError> {
         println!("==> Using Cross Validation to look for the best L2 lambda hyperparameter in values ranging from {} to {}", 
             self.hyper_range.start, 
             self.hyper_range.end);
-        
-        let mut hyperpar = 0.0;
 
         for h in self.hyper_range {
             let mut scores: Vec<f64> = Vec::new();
@@ -98,7 +144,7 @@ impl<'a> XVal<'a> {
                 let holdout_labels = training_labels.remove(ki);
 
                 // small training session (each time on a newly initialized model)
-                self.mini_train(&training_values, &training_labels, Value::new(h));
+                self.mini_train(&training_values, &training_labels, h)?;
                 
                 // holdout testing on the small training session to compute accuracy metric w.r.t current hyperpar
                 let acc = self.holdout_test(&holdout_values, &holdout_labels);
@@ -110,88 +156,53 @@ impl<'a> XVal<'a> {
 
             // checking if score's already present in HashMap
             // if not add it with the respective value (i.e. the loss and the hypervalue)
-            if !self.cv_scores.contains_key(&avg_score.to_string()) {
-                self.cv_scores.insert(avg_score.to_string(), vec![h]);
-            } else if let Some(v) = self.cv_scores.get_mut(&avg_score.to_string()) {
-                v.push(h);
-            }
+            self.cv_scores.entry(avg_score.to_string()).or_default().push(h);
         }
 
         // get the hyperpar associated with the highest accuracy (first of the list if there are more than 1)
-        if let Some(best_score) = self.cv_scores.keys().max() {
-            let hyperpars_list = self.cv_scores.get(best_score);
-
-            match hyperpars_list {
-                // get the first element from vector associated with best score
-                // these elements are all the same as they all lead to the same score
-                Some(v) => {
-                    hyperpar = v[0];
-                }
-                // if the vector's empty return a default cross validation value
-                None => {
-                    hyperpar = 1e-4;
-                }
-            }
-        }
+        // if nothing was searched return a default cross validation value
+        Ok(self.best().map_or(1e-4, |(_, hyperpars)| hyperpars[0]))
+    }
 
-        hyperpar
-    }
-
-    fn mini_train(&mut self, inputs: &Vec<Vec<[f64; 2]>>, expectations: &Vec<Vec<f64>>, hyperpar: Value) -> () {
-        self.model = Some(Model::new(self.model_arch[0], self.model_arch));
-
-        // train the new model on each of the training groups
-        for (inps, exps) in inputs.iter().zip(expectations) {
-            // train for 10 times on the same input group
-            let iterations = 10;
-            for pass in 0..iterations {
-                // prepping for new forward pass
-                self.model.as_ref().unwrap().zero_grad();
-
-                // getting predictions and losses
-                let preds: Vec<Value> = inps.iter()
-                    // .map(|i| Model::forward(self.model.as_ref().unwrap(), i))
-                    .map(|i| self.model.as_ref().unwrap().forward(i))
-                    .collect();
-                let losses: Vec<Value> = preds.iter()
-                    .zip(exps)
-                    .map(|(p, e)| (self.loss_fn)(p, *e))
-                    .collect();
-                let loss = losses.iter().sum::<Value>() / losses.len() as f64;
-
-                // normalize loss with L2
-                let reg = l2(&self.model.as_ref().unwrap().params(), Some(&hyperpar));
-                let tot_loss = loss + &reg;
-                
-                // backward pass
-                tot_loss.backward();
-                for p in self.model.as_ref().unwrap().params().iter_mut() {
-                    p.set_data(p.get_data() - ((self.alpha)(pass, iterations) * p.get_data()));
-                }            
-            }
-        }
+    // highest mean holdout accuracy (keys are compared as numbers) and the hyperpars leading to it
+    fn best(&self) -> Option<(f64, &Vec<f64>)> {
+        self.cv_scores.iter()
+            .filter_map(|(k, v)| k.parse::<f64>().ok().map(|s| (s, v)))
+            .filter(|(s, v)| !s.is_nan() && !v.is_empty())
+            .max_by(|a, b| a.0.total_cmp(&b.0))
+    }
+
+    // mean holdout accuracy of the best hyperparameter, None before searching
+    pub fn best_score(&self) -> Option<f64> {
+        self.best().map(|(s, _)| s)
     }
 
-    fn holdout_test(&self, inputs: &Vec<[f64; 2]>, expectations: &Vec<f64>) -> f64 {
-        // computing prediction on holdout value
+    fn mini_train(&mut self, inputs: &[Vec<Vec<f64>>], expectations: &[Vec<f64>], hyperpar: f64) -> Result<(), OxiError> {
+        let model = Model::try_with_activations(self.input_size, self.model_arch, &self.activations)?;
+
+        // full batch training on all of the training groups
+        let (inps, exps) = (inputs.concat(), expectations.concat());
+        self.options.l2_lambda = hyperpar;
+        let mut optimizer = match &self.optimizer {
+            Some(build) => build(),
+            None => Box::new(Sgd::new((self.alpha)(0, self.options.epochs as i32))),
+        };
+        fit(&model, optimizer.as_mut(), (&inps, &exps), None, &self.options, &mut CallbackList::default())?;
+
+        self.model = Some(model);
+        Ok(())
+    }
+
+    fn holdout_test(&self, inputs: &[Vec<f64>], expectations: &[f64]) -> f64 {
+        // computing prediction on holdout value (no graph needed as there's no backward pass)
+        let _guard = no_grad();
         let preds: Vec<Value> = inputs.iter()
             // .map(|x| Model::forward(self.model.as_ref().unwrap(), x))
             .map(|x| self.model.as_ref().unwrap().forward(x))
             .collect();
         
         // computing accuracy
-        let directions = preds.iter()
-            .zip(expectations)
-            .map(|(p, e)| 
-                if (p.get_data()>0.0) == (*e>0.0) {
-                    1.0
-                } else { 
-                    0.0
-                }
-            ).collect::<Vec<f64>>();
-        let acc: f64 = directions.iter().sum::<f64>() / directions.len() as f64;
-
-        acc
+        accuracy(&preds, expectations)
     }
 }
 
@@ -205,4 +216,78 @@ impl<'a> Display for XVal<'a> {
             // .field("CHILDREN", &self.core.borrow().children) // not printing this field as it could be pretty long, depending on the architecture of the network
             .finish()
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod test {
+    use super::*;
+    use super::super::utils::{alpha, mse};
+
+    #[test]
+    fn test_floating_range() {
+        assert_eq!(FloatingRange::new(0.0, 0.3, 0.1).count(), 4);
+        assert_eq!(FloatingRange::new(0.0, 0.3, 0.1).next(), Some(0.0));
+        assert!((FloatingRange::new(0.0, 0.3, 0.1).last().unwrap() - 0.3).abs() < 1e-12);
+        assert_eq!(FloatingRange::new(0.5, 0.5, 0.1).collect::<Vec<f64>>(), vec![0.5]);
+        assert_eq!(FloatingRange::new(0.5, 0.4, 0.1).next(), None);
+        assert_eq!(FloatingRange::new(0.0, 1.0, 0.0).next(), None);
+        assert_eq!(FloatingRange::new(0.0, 1.0, -0.1).next(), None);
+    }
+
+    #[test]
+    fn test_best_score() {
+        // both folds with positive and negative samples
+        let data = vec![vec![-1.0], vec![1.0], vec![-2.0], vec![2.0], vec![-1.5], vec![1.5], vec![-3.0], vec![3.0]];
+        let labels = vec![-1.0, 1.0, -1.0, 1.0, -1.0, 1.0, -1.0, 1.0];
+        let mut xv = XVal::new(data, labels, &[1], FloatingRange::new(0.0, 0.1, 0.1), alpha, mse, 2).unwrap();
+        assert_eq!(xv.best_score(), None);
+
+        // scores are compared as numbers, not as the strings they're keyed by
+        xv.cv_scores.insert(5e-5_f64.to_string(), vec![0.2]);
+        xv.cv_scores.insert(0.5_f64.to_string(), vec![0.1]);
+        assert_eq!(xv.best_score(), Some(0.5));
+
+        xv.cv_scores.clear();
+        let lambda = xv.search_best_hyperpar().unwrap();
+        assert!(xv.cv_scores.values().any(|v| v.contains(&lambda)));
+        assert!(xv.best_score().is_some_and(|s| s > 0.5));
+    }
+
+    #[test]
+    fn test_training_settings() {
+        use std::cell::Cell;
+        use std::rc::Rc;
+        use super::super::optim::Adam;
+
+        let data = vec![vec![-1.0], vec![-2.0], vec![1.0], vec![2.0]];
+        let built = Rc::new(Cell::new(0));
+        let counter = built.clone();
+        let mut xv = XVal::new(data, vec![-1.0, -1.0, 1.0, 1.0], &[2, 1], FloatingRange::new(0.0, 0.1, 0.1), alpha, mse, 2)
+            .unwrap()
+            .activations(&[Activation::Linear, Activation::Linear])
+            .optimizer(move || { counter.set(counter.get() + 1); Box::new(Adam::new(0.05)) })
+            .schedule(None)
+            .epochs(5);
+        xv.search_best_hyperpar().unwrap();
+
+        // a new optimizer for each fold of each hyperparameter
+        assert_eq!(built.get(), 2 * xv.cv_scores.values().map(|v| v.len()).sum::<usize>());
+        assert_eq!(xv.options.epochs, 5);
+        assert!(xv.options.schedule.is_none());
+    }
+
+    #[test]
+    fn test_errors() {
+        let data = vec![vec![-1.0], vec![1.0], vec![-2.0], vec![2.0]];
+        let range = FloatingRange::new(0.0, 0.1, 0.1);
+
+        assert!(matches!(XVal::new(data.clone(), vec![1.0], &[1], range, alpha, mse, 2), Err(OxiError::LengthMismatch { .. })));
+        assert!(matches!(XVal::new(data.clone(), vec![1.0; 4], &[1], range, alpha, mse, 5), Err(OxiError::InvalidK { k: 5, samples: 4 })));
+        assert!(matches!(XVal::new(data.clone(), vec![1.0; 4], &[1], range, alpha, mse, 0), Err(OxiError::InvalidK { k: 0, .. })));
+
+        // the activations are checked when building the model of a fold
+        let mut xv = XVal::new(data, vec![-1.0, 1.0, -1.0, 1.0], &[2, 1], range, alpha, mse, 2)
+            .unwrap()
+            .activations(&[Activation::Linear]);
+        assert!(matches!(xv.search_best_hyperpar(), Err(OxiError::ActivationCount { layers: 2, activations: 1 })));
+    }
+}